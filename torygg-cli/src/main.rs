@@ -1,17 +1,187 @@
 use std::collections::HashMap;
 use std::io::{stdin, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use clap::{Parser, Subcommand};
 use log::info;
 use simplelog::TermLogger;
 use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
 
-use torygg::{Plugin, Torygg, GroupType, FileGroup};
-use torygg::Profile;
+use torygg::{BainPackage, Plugin, Torygg, GroupType, FileGroup, InstallSource, SteamApp, Executable, Settings, Context};
+
+fn list_games(state: &Torygg) {
+    let mut stdout = StandardStream::stdout(termcolor::ColorChoice::Always);
+    for game in torygg::GAMES {
+        if game == state.game() {
+            stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green))).unwrap();
+        }
+
+        writeln!(&mut stdout, "{} ({})", game.id(), game.name()).unwrap();
+        stdout.reset().unwrap();
+    }
+}
+
+fn list_instances(current: Option<&str>) -> Result<(), torygg::Error> {
+    let instances = Context::instances()?;
+    if instances.is_empty() {
+        println!("No instances.");
+        return Ok(());
+    }
+
+    let mut stdout = StandardStream::stdout(termcolor::ColorChoice::Always);
+    for instance in &instances {
+        if Some(instance.as_str()) == current {
+            stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green))).unwrap();
+        }
+
+        writeln!(&mut stdout, "{instance}").unwrap();
+        stdout.reset().unwrap();
+    }
+
+    Ok(())
+}
+
+fn print_status(state: &Torygg) -> Result<(), torygg::Error> {
+    let game = state.game();
+    let install = state.install()?;
+
+    println!("Game: {} ({})", game.name(), game.id());
+    println!("Install directory: {}", install.install_dir().display());
+    match install.game_version() {
+        Ok(version) => println!("Version: {version}"),
+        Err(e) => println!("Version: unknown ({e})"),
+    }
+
+    match install.script_extender_version()? {
+        Some(version) => println!("Script extender: {version}"),
+        None if install.install_dir().join(game.script_extender_loader()).exists() => println!("Script extender: unknown version"),
+        None => println!("Script extender: not installed"),
+    }
+
+    if let Some(ccc_file) = game.creation_club_file() {
+        let creation_club = install.creation_club()?;
+        println!("Anniversary Edition: {}", if install.is_anniversary_edition()? { "yes" } else { "no" });
+        println!(
+            "Creation Club: {} plugins, {} archives, {} listed in {ccc_file}",
+            creation_club.plugins().len(),
+            creation_club.archives().len(),
+            creation_club.listed().len(),
+        );
+    }
+
+    println!("Profile: {}", state.profile().name());
+    println!("Deployed: {}", if state.deployed() { "yes" } else { "no" });
+    Ok(())
+}
+
+fn check(state: &Torygg) -> Result<(), torygg::Error> {
+    let reports = state.check_plugins()?;
+    if reports.is_empty() {
+        println!("No SKSE plugins.");
+        return Ok(());
+    }
+
+    let mut stdout = StandardStream::stdout(termcolor::ColorChoice::Always);
+    for report in &reports {
+        print_header(report.name());
+        for plugin in report.plugins() {
+            let color = if plugin.is_ok() { Color::Green } else { Color::Red };
+            stdout.set_color(ColorSpec::new().set_fg(Some(color))).unwrap();
+            write!(&mut stdout, "{}", plugin.path().display()).unwrap();
+            stdout.reset().unwrap();
+
+            if let Some(name) = plugin.name() {
+                print!(" ({name})");
+            }
+            if let Some(version) = plugin.version() {
+                print!(" {version}");
+            }
+            println!();
+
+            for problem in plugin.problems() {
+                println!("  {problem}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A settings value, noting when an environment variable overrides the file
+fn setting_value(settings: &Settings, key: &str) -> Result<Option<String>, torygg::Error> {
+    if let Some((var, value)) = Settings::env_var(key).and_then(|var| Some((var, std::env::var(var).ok()?))) {
+        return Ok(Some(format!("{value} (from {var})")));
+    }
+
+    settings.get(key)
+}
+
+fn config(context: &mut Context, action: ConfigAction) -> Result<(), torygg::Error> {
+    let mut settings = context.settings().clone();
+    match action {
+        ConfigAction::List => {
+            for key in Settings::keys() {
+                if let Some(value) = setting_value(&settings, &key)? {
+                    println!("{key} = {value}");
+                }
+            }
+        },
+        ConfigAction::Get { key } => match setting_value(&settings, &key)? {
+            Some(value) => println!("{value}"),
+            None => println!("{key} is not set"),
+        },
+        ConfigAction::Set { key, value } => {
+            settings.set(&key, &value)?;
+            context.set_settings(settings)?;
+        },
+        ConfigAction::Unset { key } => {
+            settings.unset(&key)?;
+            context.set_settings(settings)?;
+        },
+    }
+
+    Ok(())
+}
+
+fn inspect(state: &Torygg, archive: &Path) -> Result<(), torygg::Error> {
+    let report = state.inspect_archive(archive)?;
+    let list = |items: &[String]| if items.is_empty() { "none".to_owned() } else { items.join(", ") };
+
+    let display_root = |root: &Path| if root.as_os_str().is_empty() { "top of the archive".to_owned() } else { root.display().to_string() };
+
+    println!("Format: {}", report.format());
+    println!("Mod root: {}", display_root(report.mod_root()));
+    if report.mod_roots().len() > 1 {
+        let others = report.mod_roots().iter().skip(1).map(|root| display_root(root)).collect::<Vec<_>>();
+        println!("Other possible roots, chosen from when installing: {}", others.join(", "));
+    }
+
+    if report.fomod() {
+        println!("Layout: FOMOD installer");
+    } else if !report.bain_packages().is_empty() {
+        println!("Layout: BAIN, packages: {}", list(report.bain_packages()));
+    } else {
+        println!("Layout: plain");
+    }
+
+    println!("Data folders: {}", list(report.data_folders()));
+    println!("Plugins: {}", list(report.plugins()));
+    println!("Archives: {}", list(report.archives()));
+
+    let mut stdout = StandardStream::stdout(termcolor::ColorChoice::Always);
+    for problem in report.problems() {
+        stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red))).unwrap();
+        write!(&mut stdout, "Problem:").unwrap();
+        stdout.reset().unwrap();
+        println!(" {problem}");
+    }
+
+    Ok(())
+}
 
 fn list_profiles(state: &Torygg) -> Result<(), torygg::Error> {
     let mut stdout = StandardStream::stdout(termcolor::ColorChoice::Always);
-    for profile in Torygg::profiles()? {
+    for profile in state.profiles()? {
         if profile.name() == state.profile().name() {
             stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green))).unwrap();
         }
@@ -24,11 +194,11 @@ fn list_profiles(state: &Torygg) -> Result<(), torygg::Error> {
 }
 
 fn list_mods(state: &Torygg) -> Result<(), torygg::Error> {
-    let mods = Torygg::mods()?;
+    let mods = state.mods()?;
     if mods.is_empty() {
         println!("No mods.");
         return Ok(());
-    };
+    }
 
     let mut stdout = StandardStream::stdout(termcolor::ColorChoice::Always);
     let mut active_color = ColorSpec::new();
@@ -50,6 +220,27 @@ fn list_mods(state: &Torygg) -> Result<(), torygg::Error> {
     Ok(())
 }
 
+fn list_executables(state: &Torygg) -> Result<(), torygg::Error> {
+    let executables = state.executables()?;
+    if executables.is_empty() {
+        println!("No executables.");
+        return Ok(());
+    }
+
+    for executable in &executables {
+        print!("{}: {}", executable.name(), executable.path().display());
+        for arg in executable.args() {
+            print!(" {arg}");
+        }
+        if let Some(output_mod) = executable.output_mod() {
+            print!(" -> {output_mod}");
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
 fn print_load_order(state: &Torygg) {
     if let Some(mods) = state.active_mods() {
         for (i, m) in mods.iter().enumerate() {
@@ -72,6 +263,42 @@ fn print_header(header: &str) {
     stdout.reset().unwrap();
 }
 
+fn run(state: &mut Torygg, skse: bool, tool: Option<&str>, no_deploy: bool, keep_deployed: bool) -> Result<(), torygg::Error> {
+    let launch = state.launch(skse, tool)?;
+
+    // Only undeploy what this run deployed
+    let deploy = !no_deploy && !state.deployed();
+    if deploy {
+        state.deploy()?;
+    }
+
+    info!("Running {}", launch.executable().display());
+    let result = launch.run();
+    if deploy && !keep_deployed && state.deployed() {
+        state.undeploy()?;
+    }
+
+    result
+}
+
+fn choose_mod_root(roots: &[PathBuf]) -> Option<usize> {
+    let items = roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>();
+    dialoguer::Select::new().with_prompt("The archive contains several mods, choose the folder to install")
+        .items(&items)
+        .default(0)
+        .interact_opt().unwrap()
+}
+
+fn choose_bain_packages(packages: &[BainPackage]) -> Vec<usize> {
+    let items = packages.iter().map(BainPackage::name).collect::<Vec<_>>();
+    let defaults = packages.iter().map(BainPackage::selected).collect::<Vec<_>>();
+    dialoguer::MultiSelect::new().with_prompt("Sub-packages to install, later ones overwrite earlier ones")
+        .items(&items)
+        .defaults(&defaults)
+        .interact_opt().unwrap()
+        .unwrap_or_default()
+}
+
 fn get_input(group: &FileGroup) -> Vec<&Plugin> {
     let options = group.plugins().iter().map(|plugin| {
         (plugin.name(), plugin) }).collect::<HashMap<_, _>>();
@@ -107,6 +334,10 @@ struct Cli {
     #[arg(short, long)]
     verbose: bool,
 
+    /// use a named instance, with its own mods, profiles and settings, instead of the default setup
+    #[arg(long, global = true)]
+    instance: Option<String>,
+
     #[command(subcommand)]
     subcommand: Option<Subcommands>
 }
@@ -125,6 +356,12 @@ enum Subcommands {
         name: Option<String>,
     },
 
+    /// show what an archive contains and likely problems, without installing it
+    Inspect {
+        /// mod archive to inspect
+        archive: PathBuf,
+    },
+
     /// uninstall a mod
     Uninstall {
         /// name of mod to uninstall
@@ -152,10 +389,40 @@ enum Subcommands {
 
     LoadOrder,
 
+    /// show the current game, its versions and installed content
+    Status,
+
+    /// check the active mods' SKSE plugins against the game and SKSE versions
+    Check,
+
+    /// show or change torygg's settings
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// list the named instances
+    ListInstances,
+
+    /// list the games torygg can manage
+    ListGames,
+
+    /// switch to managing another game
+    SetGame {
+        /// id of the game, as shown by list-games
+        game: String,
+    },
+
+    /// change where the current game is installed
+    SetInstall {
+        #[command(subcommand)]
+        source: InstallArg,
+    },
+
     ListProfiles,
 
     SetProfile {
-      profile: Profile
+      profile: String
     },
 
     /// create a new profile
@@ -167,17 +434,140 @@ enum Subcommands {
     /// delete a profile
     DeleteProfile {
         /// profile to delete
-        profile: Profile,
+        profile: String,
     },
 
     Deploy,
 
     Undeploy,
+
+    /// run the game through proton, deploying the mods while it runs
+    Run {
+        /// start the game through its script extender
+        #[arg(long)]
+        skse: bool,
+
+        /// the compatibility tool to use instead of the one steam is configured with, eg. GE-Proton9-20
+        #[arg(long)]
+        tool: Option<String>,
+
+        /// run the game without deploying
+        #[arg(long)]
+        no_deploy: bool,
+
+        /// leave the mods deployed once the game exits
+        #[arg(long)]
+        keep_deployed: bool,
+    },
+
+    /// list the modding tools configured for the game
+    ListExecutables,
+
+    /// configure a modding tool to run in the games prefix
+    AddExecutable {
+        /// name to refer to the tool by
+        name: String,
+
+        /// the windows executable, relative paths are within the games install directory
+        path: PathBuf,
+
+        /// arguments passed to the tool
+        #[arg(last = true)]
+        args: Vec<String>,
+
+        /// the directory to run the tool in
+        #[arg(long)]
+        working_dir: Option<PathBuf>,
+
+        /// mod to collect files the tool writes to the data directory into
+        #[arg(long)]
+        output_mod: Option<String>,
+    },
+
+    /// remove a configured modding tool
+    RemoveExecutable {
+        /// name of the tool to remove
+        name: String,
+    },
+
+    /// run a configured modding tool with the mods deployed
+    RunExecutable {
+        /// name of the tool to run
+        name: String,
+
+        /// the compatibility tool to use instead of the one steam is configured with
+        #[arg(long)]
+        tool: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// list every setting and its value
+    List,
+
+    /// show a setting
+    Get {
+        /// the setting, eg. `deploy_method` or `games.skyrimse.prefix`
+        key: String,
+    },
+
+    /// change a setting
+    Set {
+        /// the setting, eg. `deploy_method` or `games.skyrimse.prefix`
+        key: String,
+
+        /// the new value
+        value: String,
+    },
+
+    /// remove a setting, going back to the default
+    Unset {
+        /// the setting to remove
+        key: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum InstallArg {
+    /// find the game through steam
+    Steam,
+
+    /// use a game directory and wine prefix
+    Manual {
+        /// the games install directory
+        install_dir: PathBuf,
+
+        /// the wine prefix the game runs in, the directory containing `drive_c`
+        #[arg(long)]
+        prefix: Option<PathBuf>,
+    },
+
+    /// find a GOG or Epic game installed through heroic
+    Heroic {
+        /// heroic's app name for the game
+        app_name: String,
+    },
+
+    /// find a game installed through lutris
+    Lutris {
+        /// lutris' slug for the game
+        slug: String,
+    },
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    torygg::init_default();
+impl From<InstallArg> for InstallSource {
+    fn from(arg: InstallArg) -> Self {
+        match arg {
+            InstallArg::Steam => InstallSource::Steam,
+            InstallArg::Manual { install_dir, prefix } => InstallSource::Manual { install_dir, prefix },
+            InstallArg::Heroic { app_name } => InstallSource::Heroic { app_name },
+            InstallArg::Lutris { slug } => InstallSource::Lutris { slug },
+        }
+    }
+}
 
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
     TermLogger::init(
@@ -192,7 +582,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     )
     .unwrap();
 
-    let mut state = Torygg::read_or_new();
+    match execute(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn execute(cli: Cli) -> Result<(), torygg::Error> {
+    let context = match &cli.instance {
+        Some(name) => Context::instance(name)?,
+        None => torygg::init_default()?,
+    };
+
+    let mut state = Torygg::read_or_new(context)?;
 
     match cli.subcommand {
         Some(Subcommands::ListMods) => list_mods(&state)?,
@@ -212,7 +617,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             });
 
             info!("Installing {} as {name}", archive.display());
-            Torygg::install_mod(&archive, &name, |step| -> Vec<&Plugin> {
+            state.install_mod(&archive, &name, choose_mod_root, choose_bain_packages, |step| -> Vec<&Plugin> {
                 let Some(groups) = step.file_groups() else {
                     return Vec::new()
                 };
@@ -222,18 +627,53 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             })?;
         },
 
-        Some(Subcommands::Uninstall { name }) => Torygg::uninstall_mod(&name)?,
+        Some(Subcommands::Inspect { archive }) => inspect(&state, &archive)?,
+        Some(Subcommands::Uninstall { name }) => state.uninstall_mod(&name)?,
         Some(Subcommands::Activate { name }) => state.activate_mod(&name)?,
         Some(Subcommands::Deactivate { name }) => state.deactivate_mod(&name)?,
-        Some(Subcommands::CreateMod { name }) => Torygg::create_mod(&name)?,
+        Some(Subcommands::CreateMod { name }) => state.create_mod(&name)?,
         Some(Subcommands::LoadOrder) => print_load_order(&state),
+        Some(Subcommands::Status) => print_status(&state)?,
+        Some(Subcommands::Check) => check(&state)?,
+        Some(Subcommands::Config { action }) => config(state.context_mut(), action)?,
+        Some(Subcommands::ListInstances) => list_instances(cli.instance.as_deref())?,
+        Some(Subcommands::ListGames) => list_games(&state),
+        Some(Subcommands::SetGame { game }) => {
+            let game = SteamApp::by_id(&game).ok_or(torygg::Error::GameNotFound(game))?;
+            state.set_game(game)?;
+        },
+        Some(Subcommands::SetInstall { source }) => state.set_install_source(source.into())?,
         Some(Subcommands::ListProfiles) => list_profiles(&state)?,
-        Some(Subcommands::SetProfile { profile }) => state.set_profile(profile)?,
-        Some(Subcommands::CreateProfile { name }) => { let _ = Torygg::create_profile(&name)?; },
-        Some(Subcommands::DeleteProfile { profile }) => state.delete_profile(&profile)?,
+        Some(Subcommands::SetProfile { profile }) => {
+            let profile = state.find_profile(&profile)?;
+            state.set_profile(profile)?;
+        },
+        Some(Subcommands::CreateProfile { name }) => { let _ = state.create_profile(&name)?; },
+        Some(Subcommands::DeleteProfile { profile }) => {
+            let profile = state.find_profile(&profile)?;
+            state.delete_profile(&profile)?;
+        },
         Some(Subcommands::Deploy) => state.deploy()?,
         Some(Subcommands::Undeploy) => state.undeploy()?,
+        Some(Subcommands::Run { skse, tool, no_deploy, keep_deployed }) => run(&mut state, skse, tool.as_deref(), no_deploy, keep_deployed)?,
+        Some(Subcommands::ListExecutables) => list_executables(&state)?,
+        Some(Subcommands::AddExecutable { name, path, args, working_dir, output_mod }) => {
+            let mut executable = Executable::new(&name, path).with_args(args);
+            if let Some(dir) = working_dir {
+                executable = executable.with_working_dir(dir);
+            }
+            if let Some(output_mod) = output_mod {
+                executable = executable.with_output_mod(&output_mod);
+            }
+
+            state.add_executable(executable)?;
+        },
+        Some(Subcommands::RemoveExecutable { name }) => state.remove_executable(&name)?,
+        Some(Subcommands::RunExecutable { name, tool }) => state.run_executable(&name, tool.as_deref())?,
         None => {
+            print_header("Game");
+            println!("{}", state.game().name());
+            println!();
             print_header("Profiles");
             list_profiles(&state)?;
             println!();
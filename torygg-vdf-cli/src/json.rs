@@ -0,0 +1,164 @@
+use serde_json::{Map, Number, Value};
+use torygg_vdf::{Object, Vdf};
+
+/// Keys of the single key objects that hold binary values other than ints and floats
+const POINTER: &str = "$pointer";
+const COLOR: &str = "$color";
+const UINT64: &str = "$uint64";
+const INT64: &str = "$int64";
+
+fn typed(tag: &str, value: impl Into<Value>) -> Value {
+    Value::Object(Map::from_iter([(tag.to_owned(), value.into())]))
+}
+
+/// Convert a node to JSON
+///
+/// Strings stay strings, the ints and floats of binary files become numbers and their other typed
+/// values become an object with a single key naming the type, eg. `{"$uint64": 76561197960287930}`.
+pub fn from_vdf(node: &Vdf) -> Value {
+    match node {
+        Vdf::Value(value) => Value::String(value.clone()),
+        Vdf::Object(object) => from_object(object),
+        Vdf::Int(value) => Value::from(*value),
+        Vdf::Float(value) => Number::from_f64(f64::from(*value)).map_or(Value::Null, Value::Number),
+        Vdf::Pointer(value) => typed(POINTER, *value),
+        Vdf::Color(value) => typed(COLOR, *value),
+        Vdf::UInt64(value) => typed(UINT64, *value),
+        Vdf::Int64(value) => typed(INT64, *value),
+    }
+}
+
+/// Convert an object to JSON, duplicated keys become an array of their values
+pub fn from_object(object: &Object) -> Value {
+    let mut map = Map::new();
+    for (key, value) in object {
+        let value = from_vdf(value);
+        match map.get_mut(key) {
+            Some(Value::Array(values)) => values.push(value),
+            Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+            None => {
+                map.insert(key.to_owned(), value);
+            }
+        }
+    }
+
+    Value::Object(map)
+}
+
+/// Read an object written for a typed value by [`from_vdf`]
+fn from_typed(key: &str, map: &Map<String, Value>) -> Option<Result<Vdf, String>> {
+    let [(tag, Value::Number(number))] = map.iter().collect::<Vec<_>>()[..] else {
+        return None;
+    };
+
+    let invalid = || format!("{key:?} holds {number}, which is not a valid {tag}");
+    let node = match tag.as_str() {
+        POINTER => number.as_i64().and_then(|value| i32::try_from(value).ok()).map(Vdf::Pointer),
+        COLOR => number.as_u64().and_then(|value| u32::try_from(value).ok()).map(Vdf::Color),
+        UINT64 => number.as_u64().map(Vdf::UInt64),
+        INT64 => number.as_i64().map(Vdf::Int64),
+        _ => return None,
+    };
+
+    Some(node.ok_or_else(invalid))
+}
+
+fn to_vdf(key: &str, value: Value) -> Result<Vdf, String> {
+    if let Value::Object(map) = &value {
+        if let Some(node) = from_typed(key, map) {
+            return node;
+        }
+    }
+
+    let node = match value {
+        Value::String(value) => Vdf::Value(value),
+        Value::Bool(value) => Vdf::from(if value { "1" } else { "0" }),
+        Value::Number(number) => {
+            if let Some(value) = number.as_i64() {
+                i32::try_from(value).map_or(Vdf::Int64(value), Vdf::Int)
+            } else if let Some(value) = number.as_u64() {
+                Vdf::UInt64(value)
+            } else {
+                #[allow(clippy::cast_possible_truncation)]
+                Vdf::Float(number.as_f64().unwrap_or_default() as f32)
+            }
+        }
+        Value::Object(map) => Vdf::Object(to_object(Value::Object(map))?),
+        Value::Array(_) => return Err(format!("{key:?} holds an array inside an array")),
+        Value::Null => return Err(format!("{key:?} is null")),
+    };
+
+    Ok(node)
+}
+
+/// Convert JSON back to an object, arrays become duplicated keys
+///
+/// Numbers become the typed values of binary files, which are written as plain numbers in text.
+/// Integers that fit become ints, unless they are in a typed object written by [`from_vdf`].
+pub fn to_object(value: Value) -> Result<Object, String> {
+    let Value::Object(map) = value else {
+        return Err("the JSON document must be an object".to_owned());
+    };
+
+    let mut object = Object::new();
+    for (key, value) in map {
+        if let Value::Array(values) = value {
+            for value in values {
+                object.push(key.clone(), to_vdf(&key, value)?);
+            }
+        } else {
+            let value = to_vdf(&key, value)?;
+            object.push(key, value);
+        }
+    }
+
+    Ok(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut object = Object::new();
+        object.push("name", "Skyrim");
+        object.push("appid", Vdf::Int(489_830));
+        object.push("scale", Vdf::Float(1.5));
+        object.push("whole", Vdf::Float(2.0));
+        object.push("handle", Vdf::Pointer(7));
+        object.push("tint", Vdf::Color(0xff00_00ff));
+        object.push("steamid", Vdf::UInt64(76_561_197_960_287_930));
+        object.push("small", Vdf::Int64(-1));
+        object.push("tag", "a");
+        object.push("tag", "b");
+        let mut nested = Object::new();
+        nested.push("key", "value");
+        object.push("nested", nested);
+
+        let json = from_object(&object);
+        assert_eq!(json["appid"], Value::from(489_830));
+        assert_eq!(json["tint"], serde_json::json!({ "$color": 0xff00_00ff_u32 }));
+        assert_eq!(json["tag"], serde_json::json!(["a", "b"]));
+
+        let text = serde_json::to_string(&json).unwrap();
+        assert_eq!(to_object(serde_json::from_str(&text).unwrap()).unwrap(), object);
+    }
+
+    #[test]
+    fn hand_written() {
+        let json = serde_json::json!({ "small": 1, "large": 5_000_000_000_i64, "huge": u64::MAX, "flag": true, "tag": { "$color": -1 } });
+        assert!(to_object(json).is_err());
+
+        let object = to_object(serde_json::json!({ "small": 1, "large": 5_000_000_000_i64, "huge": u64::MAX, "flag": true, "other": { "$unknown": 1 } })).unwrap();
+        assert_eq!(object.get("small"), Some(&Vdf::Int(1)));
+        assert_eq!(object.get("large"), Some(&Vdf::Int64(5_000_000_000)));
+        assert_eq!(object.get("huge"), Some(&Vdf::UInt64(u64::MAX)));
+        assert_eq!(object.get("flag"), Some(&Vdf::from("1")));
+        assert_eq!(object.get_path("other/$unknown"), Some(&Vdf::Int(1)));
+
+        assert!(to_object(serde_json::json!(["a"])).is_err());
+        assert!(to_object(serde_json::json!({ "a": [["b"]] })).is_err());
+        assert!(to_object(serde_json::json!({ "a": null })).is_err());
+    }
+}
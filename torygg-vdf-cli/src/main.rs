@@ -0,0 +1,325 @@
+mod json;
+
+use std::error::Error;
+use std::fs;
+use std::io::{stdin, stdout, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use clap::{Parser, Subcommand};
+use torygg_vdf::binary::AppInfo;
+use torygg_vdf::{Format, Mode, Object, Token, Tokenizer, Vdf};
+
+/// How a file was stored, so that it can be written back the same way
+enum Kind {
+    Text(Format),
+    Binary,
+    AppInfo,
+}
+
+fn is_appinfo(bytes: &[u8]) -> bool {
+    matches!(bytes, [0x27..=0x29, 0x44, 0x56, 0x07, ..])
+}
+
+fn read(path: &Path) -> Result<(Object, Kind), Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+
+    if is_appinfo(&bytes) {
+        let apps = AppInfo::new(Cursor::new(bytes))?.apps()?;
+        let root = apps.into_iter().map(|app| (app.appid.to_string(), Vdf::Object(app.data))).collect();
+        Ok((root, Kind::AppInfo))
+    } else if bytes.first() == Some(&0) {
+        Ok((torygg_vdf::binary::parse(&mut bytes.as_slice())?, Kind::Binary))
+    } else {
+        let text = String::from_utf8(bytes).map_err(|_| format!("{} is neither text nor binary VDF", path.display()))?;
+        let root = torygg_vdf::parse_file(path, &torygg_vdf::Options::strict())?;
+        Ok((root, Kind::Text(Format::detect(&text))))
+    }
+}
+
+/// What in a text file would be lost by writing its tree back out
+fn unwritable(text: &str) -> Option<&'static str> {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut chars = text.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '/' if !in_quotes && chars.peek() == Some(&'/') => return Some("comments"),
+            _ => (),
+        }
+    }
+
+    Tokenizer::new(text, Mode::Lenient).find_map(|token| match token {
+        Ok(Token::Conditional(_)) => Some("[$PLATFORM] conditionals"),
+        Ok(Token::Include(_) | Token::Base(_)) => Some("#include or #base directives"),
+        _ => None,
+    })
+}
+
+/// Read a file to edit it, refusing text files that would not be written back as they were
+fn read_for_editing(path: &Path) -> Result<(Object, Kind), Box<dyn Error>> {
+    let (root, kind) = read(path)?;
+    if let Kind::Text(_) = kind {
+        if let Some(lost) = unwritable(&fs::read_to_string(path)?) {
+            return Err(format!("{} contains {lost}, which editing would remove", path.display()).into());
+        }
+    }
+
+    Ok((root, kind))
+}
+
+/// Write a file through a temporary file next to it, so that it is replaced whole or not at all
+fn write(path: &Path, root: &Object, kind: &Kind) -> Result<(), Box<dyn Error>> {
+    let mut bytes = Vec::new();
+    match kind {
+        Kind::Text(format) => torygg_vdf::write(&mut bytes, root, format)?,
+        Kind::Binary => torygg_vdf::binary::write(&mut bytes, root)?,
+        Kind::AppInfo => return Err("appinfo.vdf files are read only".into()),
+    }
+
+    // Replace what a link points to rather than the link
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(&bytes)?;
+    if let Ok(metadata) = fs::metadata(&path) {
+        file.as_file().set_permissions(metadata.permissions())?;
+    }
+
+    file.persist(&path)?;
+    Ok(())
+}
+
+fn print(root: &Object, json: bool) -> Result<(), Box<dyn Error>> {
+    let mut stdout = stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut stdout, &json::from_object(root))?;
+        writeln!(stdout)?;
+    } else {
+        torygg_vdf::write(&mut stdout, root, &Format::default())?;
+    }
+
+    Ok(())
+}
+
+/// Parse a value as the same type as the node it replaces
+fn typed_like(existing: Option<&Vdf>, value: &str) -> Result<Vdf, Box<dyn Error>> {
+    let node = match existing {
+        Some(Vdf::Object(_)) => return Err("cannot replace an object with a value".into()),
+        Some(Vdf::Int(_)) => Vdf::Int(value.parse()?),
+        Some(Vdf::Float(_)) => Vdf::Float(value.parse()?),
+        Some(Vdf::Pointer(_)) => Vdf::Pointer(value.parse()?),
+        Some(Vdf::Color(_)) => Vdf::Color(value.parse()?),
+        Some(Vdf::UInt64(_)) => Vdf::UInt64(value.parse()?),
+        Some(Vdf::Int64(_)) => Vdf::Int64(value.parse()?),
+        Some(Vdf::Value(_)) | None => Vdf::from(value),
+    };
+
+    Ok(node)
+}
+
+/// Split a path into the path of its parent object and its key
+fn split(path: &str) -> (&str, &str) {
+    path.trim_matches('/').rsplit_once('/').unwrap_or(("", path.trim_matches('/')))
+}
+
+fn set(root: &mut Object, path: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    let (parent, key) = split(path);
+
+    let mut object = root;
+    for component in parent.split('/').filter(|component| !component.is_empty()) {
+        if !object.contains_key(component) {
+            object.push(component, Object::new());
+        }
+
+        object = object.get_mut(component)
+            .and_then(Vdf::as_object_mut)
+            .ok_or_else(|| format!("{component} is not an object"))?;
+    }
+
+    let value = typed_like(object.get(key), value)?;
+    object.insert(key, value);
+    Ok(())
+}
+
+fn delete(root: &mut Object, path: &str) -> Result<(), Box<dyn Error>> {
+    let (parent, key) = split(path);
+    let object = if parent.is_empty() {
+        Some(root)
+    } else {
+        root.get_path_mut(parent).and_then(Vdf::as_object_mut)
+    };
+
+    object.and_then(|object| object.remove(key))
+        .map(|_| ())
+        .ok_or_else(|| format!("{path} not found").into())
+}
+
+#[derive(Parser)]
+#[command(author, version, about = "Query and edit text and binary VDF files", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    subcommand: Subcommands,
+}
+
+#[derive(Subcommand)]
+enum Subcommands {
+    /// print a value, or an object as VDF
+    Get {
+        file: PathBuf,
+
+        /// `/` separated path, eg. libraryfolders/0/path
+        path: String,
+
+        /// print objects as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// set a value, creating any missing objects on the way
+    Set {
+        file: PathBuf,
+
+        /// `/` separated path, eg. libraryfolders/0/path
+        path: String,
+
+        value: String,
+    },
+
+    /// delete a value or object
+    Delete {
+        file: PathBuf,
+
+        /// `/` separated path, eg. libraryfolders/0/apps/489830
+        path: String,
+    },
+
+    /// print a whole file
+    Dump {
+        file: PathBuf,
+
+        /// print as JSON, duplicated keys become arrays
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// convert JSON, as written by `dump --json`, to VDF
+    FromJson {
+        /// JSON file to read, or - for stdin
+        input: PathBuf,
+
+        /// VDF file to write
+        output: PathBuf,
+
+        /// write binary rather than text VDF
+        #[arg(long)]
+        binary: bool,
+    },
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    match cli.subcommand {
+        Subcommands::Get { file, path, json } => {
+            let (root, _) = read(&file)?;
+            match root.get_path(&path) {
+                Some(Vdf::Object(object)) => print(object, json)?,
+                Some(node) if json => println!("{}", json::from_vdf(node)),
+                Some(node) => println!("{}", node.to_value_string().unwrap_or_default()),
+                None => return Err(format!("{path} not found").into()),
+            }
+        }
+        Subcommands::Set { file, path, value } => {
+            let (mut root, kind) = read_for_editing(&file)?;
+            set(&mut root, &path, &value)?;
+            write(&file, &root, &kind)?;
+        }
+        Subcommands::Delete { file, path } => {
+            let (mut root, kind) = read_for_editing(&file)?;
+            delete(&mut root, &path)?;
+            write(&file, &root, &kind)?;
+        }
+        Subcommands::Dump { file, json } => {
+            let (root, _) = read(&file)?;
+            print(&root, json)?;
+        }
+        Subcommands::FromJson { input, output, binary } => {
+            let text = if input == Path::new("-") {
+                let mut text = String::new();
+                stdin().read_to_string(&mut text)?;
+                text
+            } else {
+                fs::read_to_string(&input)?
+            };
+
+            let root = json::to_object(serde_json::from_str(&text)?)?;
+            let kind = if binary { Kind::Binary } else { Kind::Text(Format::default()) };
+            write(&output, &root, &kind)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(text: &str) -> Object {
+        torygg_vdf::parse_str(text, &torygg_vdf::Options::strict()).unwrap()
+    }
+
+    #[test]
+    fn edit() {
+        assert_eq!(split("/a/b/c/"), ("a/b", "c"));
+        assert_eq!(split("a"), ("", "a"));
+
+        let mut root = object("libraryfolders { 0 { path /games apps { 489830 0 } } }");
+        set(&mut root, "libraryfolders/0/path", "/mnt/games").unwrap();
+        set(&mut root, "libraryfolders/1/path", "/other").unwrap();
+        assert_eq!(root.get_path("libraryfolders/0/path").and_then(Vdf::as_str), Some("/mnt/games"));
+        assert_eq!(root.get_path("libraryfolders/1/path").and_then(Vdf::as_str), Some("/other"));
+        assert!(set(&mut root, "libraryfolders/0", "x").is_err());
+        assert!(set(&mut root, "libraryfolders/0/path/deeper", "x").is_err());
+
+        delete(&mut root, "libraryfolders/0/apps/489830").unwrap();
+        assert!(root.get_path("libraryfolders/0/apps/489830").is_none());
+        assert!(delete(&mut root, "libraryfolders/0/apps/489830").is_err());
+        delete(&mut root, "libraryfolders").unwrap();
+        assert!(root.is_empty());
+
+        let mut binary = Object::new();
+        binary.push("appid", Vdf::Int(1));
+        set(&mut binary, "appid", "489830").unwrap();
+        assert_eq!(binary.get("appid"), Some(&Vdf::Int(489_830)));
+        assert!(set(&mut binary, "appid", "skyrim").is_err());
+    }
+
+    #[test]
+    fn files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/other.vdf"), "included { key value }").unwrap();
+        fs::write(dir.path().join("sub/main.vdf"), "#include other.vdf\nroot { key value }").unwrap();
+        fs::write(dir.path().join("commented.vdf"), "root { key value } // note\nurl \"https://example.com\"").unwrap();
+        fs::write(dir.path().join("plain.vdf"), "\"root\"\n{\n\t\"key\"\t\t\"value\"\n\t\"url\"\t\t\"https://example.com\"\n}\n").unwrap();
+
+        // Includes are relative to the file, not the current directory
+        let (root, _) = read(&dir.path().join("sub/main.vdf")).unwrap();
+        assert_eq!(root.get_path("included/key").and_then(Vdf::as_str), Some("value"));
+
+        assert!(read_for_editing(&dir.path().join("sub/main.vdf")).is_err());
+        assert!(read_for_editing(&dir.path().join("commented.vdf")).is_err());
+        assert!(unwritable("a [$WIN32] b").is_some());
+
+        let path = dir.path().join("plain.vdf");
+        let (mut root, kind) = read_for_editing(&path).unwrap();
+        set(&mut root, "root/key", "changed").unwrap();
+        write(&path, &root, &kind).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "\"root\"\n{\n\t\"key\"\t\t\"changed\"\n\t\"url\"\t\t\"https://example.com\"\n}\n");
+        // No temporary file is left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+}
@@ -1,7 +1,8 @@
-/// Finds the libraryfolder that a game is in
-/// Takes the wanted game's appid as its first and only arg
+//! Finds the libraryfolder that a game is in
+//! Takes the wanted game's appid as its first and only arg
 
 use std::{fs::File, path::PathBuf};
+use torygg_vdf::Vdf;
 
 fn main() -> Result<(), &'static str> {
     let args = std::env::args().collect::<Vec<_>>();
@@ -16,20 +17,19 @@ fn main() -> Result<(), &'static str> {
 
     let vdf = PathBuf::from(std::env::var("HOME").map_err(|_| "failed to find home dir")?)
         .join(".steam/root/config/libraryfolders.vdf");
-    let kvs =
-        torygg_vdf::parse(&mut File::open(vdf).map_err(|_| "failed to open libraryfolders.vdf")?)
+    let root =
+        torygg_vdf::parse_tree(&mut File::open(vdf).map_err(|_| "failed to open libraryfolders.vdf")?)
             .map_err(|_| "failed to parse libraryfolders.vdf")?;
 
-    for kv in &kvs {
-        let components = kv.0.iter().collect::<Vec<_>>();
-        // Key we want:                    🠗
-        // libraryfolders/<lib_id>/apps/<appid>
-        if let Some(component) = components.get(3) {
-            if *component == appid.as_str() {
-                // libraryfolders/<lib_id>/path
-                let path = PathBuf::from_iter(kv.0.iter().take(2)).join("path");
+    let folders = root.get("libraryfolders").and_then(Vdf::as_object)
+        .ok_or("no libraryfolders in libraryfolders.vdf")?;
 
-                println!("{}", &kvs[&path]);
+    for (_, folder) in folders {
+        // libraryfolders/<lib_id>/apps/<appid>
+        if folder.get("apps").and_then(|apps| apps.get(appid)).is_some() {
+            // libraryfolders/<lib_id>/path
+            if let Some(path) = folder.get("path").and_then(Vdf::as_str) {
+                println!("{path}");
                 return Ok(());
             }
         }
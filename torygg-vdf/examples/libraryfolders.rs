@@ -1,21 +1,22 @@
-/// Prints the paths of all steam libraries
+//! Prints the paths of all steam libraries
 
 use std::{fs::File, path::PathBuf};
+use torygg_vdf::Vdf;
+
 fn main() -> Result<(), &'static str> {
     let vdf = PathBuf::from(std::env::var("HOME").map_err(|_| "failed to find home dir")?)
         .join(".steam/root/config/libraryfolders.vdf");
-    let kvs =
-        torygg_vdf::parse(&mut File::open(vdf).map_err(|_| "failed to open libraryfolders.vdf")?)
+    let root =
+        torygg_vdf::parse_tree(&mut File::open(vdf).map_err(|_| "failed to open libraryfolders.vdf")?)
             .map_err(|_| "failed to parse libraryfolders.vdf")?;
-    
-    for kv in kvs {
-        let components = kv.0.iter().collect::<Vec<_>>();
-        // Keys we want:            🠗
-        // libraryfolders/<lib_id>/path
-        if let Some(component) = components.get(2) {
-            if *component == "path" {
-                println!("{}", kv.1);
-            }
+
+    let folders = root.get("libraryfolders").and_then(Vdf::as_object)
+        .ok_or("no libraryfolders in libraryfolders.vdf")?;
+
+    // libraryfolders/<lib_id>/path
+    for (_, folder) in folders {
+        if let Some(path) = folder.get("path").and_then(Vdf::as_str) {
+            println!("{path}");
         }
     }
 
@@ -0,0 +1,291 @@
+//! Binary KeyValues, as used by `shortcuts.vdf` and `appcache/appinfo.vdf`
+
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use crate::{Object, Vdf, MAX_DEPTH};
+
+const TYPE_OBJECT: u8 = 0x00;
+const TYPE_STRING: u8 = 0x01;
+const TYPE_INT: u8 = 0x02;
+const TYPE_FLOAT: u8 = 0x03;
+const TYPE_POINTER: u8 = 0x04;
+const TYPE_WIDE_STRING: u8 = 0x05;
+const TYPE_COLOR: u8 = 0x06;
+const TYPE_UINT64: u8 = 0x07;
+const TYPE_END: u8 = 0x08;
+const TYPE_INT64: u8 = 0x0A;
+const TYPE_ALTERNATE_END: u8 = 0x0B;
+
+fn invalid_data<S: Into<String>>(message: S) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> Result<[u8; N]> {
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_array(reader)?))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> Result<String> {
+    let mut bytes = Vec::new();
+    loop {
+        let [byte] = read_array(reader)?;
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+    }
+
+    String::from_utf8(bytes).map_err(|_| invalid_data("string is not valid utf-8"))
+}
+
+fn read_wide_string<R: Read>(reader: &mut R) -> Result<String> {
+    let mut units = Vec::new();
+    loop {
+        let unit = u16::from_le_bytes(read_array(reader)?);
+        if unit == 0 {
+            break;
+        }
+        units.push(unit);
+    }
+
+    String::from_utf16(&units).map_err(|_| invalid_data("wide string is not valid utf-16"))
+}
+
+/// Where keys are read from, newer `appinfo.vdf` files store keys in a shared string table
+enum Keys<'a> {
+    Inline,
+    Table(&'a [String]),
+}
+
+impl Keys<'_> {
+    fn read<R: Read>(&self, reader: &mut R) -> Result<String> {
+        match self {
+            Self::Inline => read_string(reader),
+            Self::Table(table) => {
+                let index = read_u32(reader)?;
+                usize::try_from(index).ok()
+                    .and_then(|index| table.get(index))
+                    .cloned()
+                    .ok_or_else(|| invalid_data(format!("key index {index} is out of range")))
+            }
+        }
+    }
+}
+
+/// `depth` is the number of objects `reader` is already in, deeper nesting is rejected before it can overflow the stack
+fn read_object<R: Read>(reader: &mut R, keys: &Keys, root: bool, depth: usize) -> Result<Object> {
+    if depth > MAX_DEPTH {
+        return Err(invalid_data(format!("objects are nested more than {MAX_DEPTH} deep")));
+    }
+
+    let mut object = Object::new();
+    loop {
+        let mut kind = [0];
+        if reader.read(&mut kind)? == 0 {
+            if root {
+                return Ok(object);
+            }
+            return Err(Error::from(ErrorKind::UnexpectedEof));
+        }
+
+        let [kind] = kind;
+        if kind == TYPE_END || kind == TYPE_ALTERNATE_END {
+            return Ok(object);
+        }
+
+        let key = keys.read(reader)?;
+        let value = match kind {
+            TYPE_OBJECT => Vdf::Object(read_object(reader, keys, false, depth + 1)?),
+            TYPE_STRING => Vdf::Value(read_string(reader)?),
+            TYPE_INT => Vdf::Int(i32::from_le_bytes(read_array(reader)?)),
+            TYPE_FLOAT => Vdf::Float(f32::from_le_bytes(read_array(reader)?)),
+            TYPE_POINTER => Vdf::Pointer(i32::from_le_bytes(read_array(reader)?)),
+            TYPE_WIDE_STRING => Vdf::Value(read_wide_string(reader)?),
+            TYPE_COLOR => Vdf::Color(u32::from_le_bytes(read_array(reader)?)),
+            TYPE_UINT64 => Vdf::UInt64(u64::from_le_bytes(read_array(reader)?)),
+            TYPE_INT64 => Vdf::Int64(i64::from_le_bytes(read_array(reader)?)),
+            kind => return Err(invalid_data(format!("unknown value type {kind:#04x} for key \"{key}\""))),
+        };
+
+        object.push(key, value);
+    }
+}
+
+/// Parse a binary buffer, such as `shortcuts.vdf`.
+/// Returns the root object, which holds the top level keys.
+///
+/// # Errors
+/// Errors when reading fails or the buffer is not valid binary KeyValues
+pub fn parse<R: Read>(reader: &mut R) -> Result<Object> {
+    read_object(reader, &Keys::Inline, true, 0)
+}
+
+fn write_object<W: Write>(writer: &mut W, object: &Object) -> Result<()> {
+    for (key, value) in object {
+        let kind = match value {
+            Vdf::Object(_) => TYPE_OBJECT,
+            Vdf::Value(_) => TYPE_STRING,
+            Vdf::Int(_) => TYPE_INT,
+            Vdf::Float(_) => TYPE_FLOAT,
+            Vdf::Pointer(_) => TYPE_POINTER,
+            Vdf::Color(_) => TYPE_COLOR,
+            Vdf::UInt64(_) => TYPE_UINT64,
+            Vdf::Int64(_) => TYPE_INT64,
+        };
+
+        writer.write_all(&[kind])?;
+        writer.write_all(key.as_bytes())?;
+        writer.write_all(&[0])?;
+
+        match value {
+            Vdf::Object(object) => write_object(writer, object)?,
+            Vdf::Value(value) => {
+                writer.write_all(value.as_bytes())?;
+                writer.write_all(&[0])?;
+            }
+            Vdf::Int(int) | Vdf::Pointer(int) => writer.write_all(&int.to_le_bytes())?,
+            Vdf::Float(float) => writer.write_all(&float.to_le_bytes())?,
+            Vdf::Color(color) => writer.write_all(&color.to_le_bytes())?,
+            Vdf::UInt64(int) => writer.write_all(&int.to_le_bytes())?,
+            Vdf::Int64(int) => writer.write_all(&int.to_le_bytes())?,
+        }
+    }
+
+    writer.write_all(&[TYPE_END])
+}
+
+/// Write a document as binary KeyValues
+///
+/// Strings are always written as UTF-8, wide strings are not written back out.
+///
+/// # Errors
+/// Errors when writing fails
+pub fn write<W: Write>(writer: &mut W, root: &Object) -> Result<()> {
+    write_object(writer, root)
+}
+
+const APPINFO_V27: u32 = 0x07564427;
+const APPINFO_V28: u32 = 0x07564428;
+const APPINFO_V29: u32 = 0x07564429;
+
+/// An app from `appcache/appinfo.vdf`
+#[derive(Debug, Clone, PartialEq)]
+pub struct App {
+    pub appid: u32,
+    pub info_state: u32,
+    pub last_updated: u32,
+    pub pics_token: u64,
+    pub change_number: u32,
+    /// The app's key values, the interesting parts are below the `appinfo` key
+    pub data: Object,
+}
+
+/// Reader for `appcache/appinfo.vdf`
+///
+/// Apps are read on demand as the file holds every app Steam knows about.
+pub struct AppInfo<R> {
+    reader: R,
+    version: u32,
+    universe: u32,
+    strings: Option<Vec<String>>,
+    first_app: u64,
+}
+
+impl<R: Read + Seek> AppInfo<R> {
+    /// Read the header of an `appinfo.vdf`
+    ///
+    /// # Errors
+    /// Errors when reading fails or the file is not a supported version
+    pub fn new(mut reader: R) -> Result<Self> {
+        let version = read_u32(&mut reader)?;
+        if ![APPINFO_V27, APPINFO_V28, APPINFO_V29].contains(&version) {
+            return Err(invalid_data(format!("unsupported appinfo version {version:#010x}")));
+        }
+
+        let universe = read_u32(&mut reader)?;
+
+        let strings = if version >= APPINFO_V29 {
+            let offset = u64::from_le_bytes(read_array(&mut reader)?);
+            let first_app = reader.stream_position()?;
+
+            reader.seek(SeekFrom::Start(offset))?;
+            let count = read_u32(&mut reader)?;
+            let strings = (0..count).map(|_| read_string(&mut reader)).collect::<Result<Vec<_>>>()?;
+
+            reader.seek(SeekFrom::Start(first_app))?;
+            Some(strings)
+        } else {
+            None
+        };
+
+        let first_app = reader.stream_position()?;
+        Ok(Self { reader, version, universe, strings, first_app })
+    }
+
+    #[must_use]
+    pub fn universe(&self) -> u32 {
+        self.universe
+    }
+
+    /// Visit each app in turn, `wanted` decides from the appid whether the app's data is read
+    fn scan<F: FnMut(u32) -> bool>(&mut self, mut wanted: F, apps: &mut Vec<App>, first_only: bool) -> Result<()> {
+        self.reader.seek(SeekFrom::Start(self.first_app))?;
+
+        loop {
+            let appid = read_u32(&mut self.reader)?;
+            if appid == 0 {
+                return Ok(());
+            }
+
+            let size = read_u32(&mut self.reader)?;
+            let next_app = self.reader.stream_position()? + u64::from(size);
+            if !wanted(appid) {
+                self.reader.seek(SeekFrom::Start(next_app))?;
+                continue;
+            }
+
+            let info_state = read_u32(&mut self.reader)?;
+            let last_updated = read_u32(&mut self.reader)?;
+            let pics_token = u64::from_le_bytes(read_array(&mut self.reader)?);
+            let _sha1: [u8; 20] = read_array(&mut self.reader)?;
+            let change_number = read_u32(&mut self.reader)?;
+            if self.version >= APPINFO_V28 {
+                let _binary_sha1: [u8; 20] = read_array(&mut self.reader)?;
+            }
+
+            let keys = self.strings.as_deref().map_or(Keys::Inline, Keys::Table);
+            let data = read_object(&mut self.reader, &keys, false, 0)?;
+
+            apps.push(App { appid, info_state, last_updated, pics_token, change_number, data });
+            if first_only {
+                return Ok(());
+            }
+
+            self.reader.seek(SeekFrom::Start(next_app))?;
+        }
+    }
+
+    /// Find a single app
+    ///
+    /// # Errors
+    /// Errors when reading fails or the app's data is malformed
+    pub fn find(&mut self, appid: u32) -> Result<Option<App>> {
+        let mut apps = Vec::new();
+        self.scan(|id| id == appid, &mut apps, true)?;
+        Ok(apps.pop())
+    }
+
+    /// Read every app
+    ///
+    /// # Errors
+    /// Errors when reading fails or an app's data is malformed
+    pub fn apps(&mut self) -> Result<Vec<App>> {
+        let mut apps = Vec::new();
+        self.scan(|_| true, &mut apps, false)?;
+        Ok(apps)
+    }
+}
@@ -0,0 +1,40 @@
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VdfError {
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+
+    #[error("line {line}, column {column}: expected {expected}, found {found}")]
+    Syntax {
+        line: usize,
+        column: usize,
+        expected: &'static str,
+        found: String,
+    },
+
+    #[error("in {path:?}: {source}")]
+    Include {
+        path: PathBuf,
+        source: Box<VdfError>,
+    },
+
+    #[error("{0:?} includes itself")]
+    IncludeCycle(PathBuf),
+}
+
+impl VdfError {
+    /// The line and column of a syntax error, both starting at 1
+    ///
+    /// For errors in an included file this is the position in that file.
+    #[must_use]
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Syntax { line, column, .. } => Some((*line, *column)),
+            Self::Include { source, .. } => source.position(),
+            Self::IOError(_) | Self::IncludeCycle(_) => None,
+        }
+    }
+}
@@ -2,9 +2,7 @@
 mod tests {
     use std::path::PathBuf;
 
-    #[test]
-    fn parse() {
-        let buf = "\
+    const BASEGROUP: &str = "\
 \"basegroup\"
 {
     \"key5\" \"value5\"
@@ -22,7 +20,9 @@ mod tests {
 }
 ";
 
-        let kv = crate::parse(&mut buf.as_bytes()).unwrap();
+    #[test]
+    fn parse() {
+        let kv = crate::parse(&mut BASEGROUP.as_bytes()).unwrap();
         assert_eq!(kv[&PathBuf::from("basegroup/key5")], "value5");
         assert_eq!(kv[&PathBuf::from("basegroup/empty")], "");
         assert_eq!(kv[&PathBuf::from("basegroup/key3")], "value3");
@@ -30,68 +30,400 @@ mod tests {
         assert_eq!(kv[&PathBuf::from("basegroup/othersubgroup/key2")], "value2");
         assert_eq!(kv[&PathBuf::from("basegroup/key4")], "value\n4");
     }
+
+    #[test]
+    fn parse_tree() {
+        let buf = "\
+\"root\"
+{
+    \"b\" \"1\"
+    \"a\" \"2\"
+    \"apps\"
+    {
+        \"489830\" \"100\"
+        \"72850\" \"200\"
+    }
+    \"B\" \"3\"
+}
+";
+
+        let root = crate::parse_tree(&mut buf.as_bytes()).unwrap();
+        let object = root.get("root").and_then(crate::Vdf::as_object).unwrap();
+        assert_eq!(object.keys().collect::<Vec<_>>(), ["b", "a", "apps", "B"]);
+        assert_eq!(object.get_all("b").filter_map(crate::Vdf::as_str).collect::<Vec<_>>(), ["1", "3"]);
+
+        let apps = root.get_path("root/apps").and_then(crate::Vdf::as_object).unwrap();
+        assert_eq!(apps.keys().collect::<Vec<_>>(), ["489830", "72850"]);
+        assert_eq!(root.get_path("root/apps/72850").and_then(crate::Vdf::as_str), Some("200"));
+        assert_eq!(root.flatten()[&PathBuf::from("root/B")], "3");
+    }
+
+    #[test]
+    fn malformed() {
+        fn strict(buf: &str) -> Option<(usize, usize)> {
+            crate::parse_tree_with(&mut buf.as_bytes(), &crate::Options::strict()).unwrap_err().position()
+        }
+
+        // Unbalanced braces
+        let buf = "\"root\"\n{\n    \"key\" \"value\"\n";
+        assert_eq!(strict(buf), Some((4, 1)));
+        assert_eq!(crate::parse(&mut buf.as_bytes()).unwrap()[&PathBuf::from("root/key")], "value");
+
+        // Unterminated quote
+        let buf = "\"root\"\n{\n    \"key\" \"value\n}\n";
+        assert_eq!(strict(buf), Some((3, 11)));
+        assert!(crate::parse(&mut buf.as_bytes()).unwrap().is_empty());
+
+        // Stray closing brace at the root
+        let buf = "\"root\" { }\n}\n\"key\" \"value\"";
+        assert_eq!(strict(buf), Some((2, 1)));
+        assert_eq!(crate::parse(&mut buf.as_bytes()).unwrap()[&PathBuf::from("key")], "value");
+
+        // Dangling key
+        let buf = "\"root\"\n{\n    \"key\"\n}\n";
+        assert_eq!(strict(buf), Some((4, 1)));
+        assert!(crate::parse_tree(&mut buf.as_bytes()).unwrap().get("root").is_some());
+
+        // A backslash before a newline is not an escape sequence
+        assert_eq!(strict("\"a\\\n\" \"b\""), Some((1, 3)));
+        assert!(crate::parse_tree(&mut "\"a\\\n\" \"b\"".as_bytes()).is_ok());
+
+        let error = crate::parse_tree_with(&mut "\"a\" \"b\" x".as_bytes(), &crate::Options::strict()).unwrap_err();
+        assert_eq!(error.to_string(), "line 1, column 10: expected value or '{', found end of input");
+
+        assert!(crate::parse_tree_with(&mut BASEGROUP.as_bytes(), &crate::Options::strict()).is_ok());
+    }
+
+    #[test]
+    fn tokenizer() {
+        use std::borrow::Cow;
+        use crate::Token;
+
+        let buf = "\"root\" { key \"a \\\"b\\\"\" sub { } [$WIN32]";
+        let tokens = crate::Tokenizer::new(buf, crate::Mode::Lenient).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(tokens, [
+            Token::Key("root".into()),
+            Token::ObjectStart,
+            Token::Key("key".into()),
+            Token::Value("a \"b\"".into()),
+            Token::Key("sub".into()),
+            Token::ObjectStart,
+            Token::ObjectEnd,
+            Token::Conditional("$WIN32"),
+            Token::ObjectEnd,
+        ]);
+        assert!(matches!(tokens[0], Token::Key(Cow::Borrowed(_))));
+        assert!(matches!(tokens[3], Token::Value(Cow::Owned(_))));
+
+        let mut strict = crate::Tokenizer::new("a { b", crate::Mode::Strict);
+        assert!(strict.by_ref().take(3).all(|token| token.is_ok()));
+        assert_eq!(strict.next().unwrap().unwrap_err().position(), Some((1, 6)));
+        assert!(strict.next().is_none());
+
+        assert_eq!(crate::unescape("C:\\\\Games\\tx"), "C:\\Games\tx");
+        assert!(matches!(crate::unescape("plain"), Cow::Borrowed("plain")));
+    }
+
+    #[test]
+    fn keyvalues_syntax() {
+        let buf = "\
+// A game config
+GameInfo
+{
+    game \"Skyrim\" // trailing comment
+    type singleplayer_only
+    \"exe\" \"skyrim.exe\" [$WIN32]
+    \"exe\" \"skyrim\" [!$WIN32]
+    FileSystem [$LINUX || $OSX]
+    {
+        SearchPaths { game |gameinfo_path|. }
+    }
+    \"windowed\" [$WIN32 && $LINUX] \"1\"
+    fullscreen[$WIN32] 1
+    borderless 1[$LINUX]
+}
+";
+        let linux = crate::parse_tree_with(&mut buf.as_bytes(), &crate::Options::strict().with_platforms(["LINUX", "POSIX"])).unwrap();
+        assert_eq!(linux.get_path("GameInfo/game").and_then(crate::Vdf::as_str), Some("Skyrim"));
+        assert_eq!(linux.get_path("GameInfo/type").and_then(crate::Vdf::as_str), Some("singleplayer_only"));
+        assert_eq!(linux.get_path("GameInfo/exe").and_then(crate::Vdf::as_str), Some("skyrim"));
+        assert_eq!(linux.get_path("GameInfo/FileSystem/SearchPaths/game").and_then(crate::Vdf::as_str), Some("|gameinfo_path|."));
+        assert!(linux.get_path("GameInfo/windowed").is_none());
+        assert!(linux.get_path("GameInfo/fullscreen").is_none());
+        assert_eq!(linux.get_path("GameInfo/borderless").and_then(crate::Vdf::as_str), Some("1"));
+
+        let windows = crate::parse_tree_with(&mut buf.as_bytes(), &crate::Options::strict().with_platforms(["WIN32"])).unwrap();
+        assert_eq!(windows.get_path("GameInfo/exe").and_then(crate::Vdf::as_str), Some("skyrim.exe"));
+        assert!(windows.get_path("GameInfo/FileSystem").is_none());
+        assert_eq!(windows.get_path("GameInfo/fullscreen").and_then(crate::Vdf::as_str), Some("1"));
+        assert!(windows.get_path("GameInfo/borderless").is_none());
+
+        // The platform this runs on by default
+        let host = crate::parse_tree(&mut buf.as_bytes()).unwrap();
+        assert_eq!(host.get_path("GameInfo/FileSystem").is_some(), cfg!(any(target_os = "linux", target_os = "macos")));
+        assert_eq!(host.get_path("GameInfo/exe").and_then(crate::Vdf::as_str), Some(if cfg!(windows) { "skyrim.exe" } else { "skyrim" }));
+
+        let dir = std::env::temp_dir().join(format!("torygg-vdf-includes-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("base")).unwrap();
+        std::fs::write(dir.join("base/defaults.vdf"), "settings { volume 5 fov 70 } extra { a b }").unwrap();
+        std::fs::write(dir.join("more.vdf"), "included { c d }").unwrap();
+        std::fs::write(dir.join("main.vdf"), "#base \"base/defaults.vdf\"\n#include more.vdf\nsettings { fov 90 }").unwrap();
+        std::fs::write(dir.join("loop.vdf"), "#include loop.vdf").unwrap();
+        std::fs::write(dir.join("indirect.vdf"), "#include base/../indirect.vdf").unwrap();
+
+        let root = crate::parse_file(dir.join("main.vdf"), &crate::Options::strict()).unwrap();
+        assert_eq!(root.keys().collect::<Vec<_>>(), ["settings", "extra", "included"]);
+        assert_eq!(root.get_path("settings/fov").and_then(crate::Vdf::as_str), Some("90"));
+        assert_eq!(root.get_path("settings/volume").and_then(crate::Vdf::as_str), Some("5"));
+        assert!(crate::parse_file(dir.join("loop.vdf"), &crate::Options::default()).is_err());
+        assert!(matches!(crate::parse_file(dir.join("indirect.vdf"), &crate::Options::default()), Err(crate::VdfError::IncludeCycle(_))));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn round_trip() {
+        let root = crate::parse_tree(&mut BASEGROUP.as_bytes()).unwrap();
+        let format = crate::Format::detect(BASEGROUP);
+        assert_eq!(crate::to_string(&root, &format), BASEGROUP);
+
+        let steam = "\
+\"libraryfolders\"
+{
+\t\"0\"
+\t{
+\t\t\"path\"\t\t\"C:\\\\Program Files (x86)\\\\Steam\"
+\t\t\"label\"\t\t\"say \\\"hi\\\"\\n\"
+\t\t\"apps\"
+\t\t{
+\t\t}
+\t}
+}
+";
+        let root = crate::parse_tree(&mut steam.as_bytes()).unwrap();
+        assert_eq!(root.get_path("libraryfolders/0/path").and_then(crate::Vdf::as_str), Some("C:\\Program Files (x86)\\Steam"));
+        assert_eq!(crate::Format::detect(steam), crate::Format::default());
+        assert_eq!(root.to_string(), steam);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut shortcut = crate::Object::new();
+        shortcut.push("appid", crate::Vdf::Int(-1_234_567));
+        shortcut.push("AppName", "Skyrim (GOG)");
+        shortcut.push("Exe", "\"/games/skyrim/SkyrimSE.exe\"");
+        shortcut.push("LastPlayTime", crate::Vdf::Int(0));
+        shortcut.push("tags", crate::Object::new());
+        let shortcuts = std::iter::once(("0", shortcut)).collect::<crate::Object>();
+        let root = std::iter::once(("shortcuts", shortcuts)).collect::<crate::Object>();
+
+        let mut buf = Vec::new();
+        crate::binary::write(&mut buf, &root).unwrap();
+        assert!(buf.starts_with(b"\x00shortcuts\x00\x000\x00\x02appid\x00"));
+        assert!(buf.ends_with(b"\x08\x08\x08"));
+        assert_eq!(crate::binary::parse(&mut buf.as_slice()).unwrap(), root);
+        assert_eq!(root.get_path("shortcuts/0/appid").and_then(crate::Vdf::as_i64), Some(-1_234_567));
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let mut buf = [0x00, b'a', 0].repeat(crate::MAX_DEPTH + 1);
+        buf.extend([0x08].repeat(crate::MAX_DEPTH + 2));
+        let error = crate::binary::parse(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        let text = "a {".repeat(crate::MAX_DEPTH + 1) + &"}".repeat(crate::MAX_DEPTH + 1);
+        let error = crate::parse_str(&text, &crate::Options::default()).unwrap_err();
+        assert_eq!(error.position(), Some((1, crate::MAX_DEPTH * 3 + 3)));
+
+        let text = "a {".repeat(crate::MAX_DEPTH) + &"}".repeat(crate::MAX_DEPTH);
+        assert!(crate::parse_str(&text, &crate::Options::default()).is_ok());
+    }
+
+    #[test]
+    fn appinfo() {
+        fn app(buf: &mut Vec<u8>, appid: u32, kv: &[u8]) {
+            buf.extend(appid.to_le_bytes());
+            buf.extend(u32::try_from(60 + kv.len()).unwrap().to_le_bytes());
+            buf.extend(2u32.to_le_bytes()); // info state
+            buf.extend(1_700_000_000u32.to_le_bytes()); // last updated
+            buf.extend(0u64.to_le_bytes()); // pics token
+            buf.extend([0; 20]); // sha1
+            buf.extend(42u32.to_le_bytes()); // change number
+            buf.extend([0; 20]); // binary sha1
+            buf.extend(kv);
+        }
+
+        // Keys are indices into the string table: 0 = appinfo, 1 = config, 2 = installdir
+        let mut buf = Vec::new();
+        buf.extend(0x07564429u32.to_le_bytes());
+        buf.extend(1u32.to_le_bytes());
+        let offset_position = buf.len();
+        buf.extend(0u64.to_le_bytes());
+        app(&mut buf, 72850, b"\x00\x00\x00\x00\x00\x08\x08");
+        app(&mut buf, 489830, b"\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x01\x02\x00\x00\x00Skyrim Special Edition\x00\x08\x08\x08");
+        buf.extend(0u32.to_le_bytes());
+        let offset = u64::try_from(buf.len()).unwrap().to_le_bytes();
+        buf[offset_position..offset_position + 8].copy_from_slice(&offset);
+        buf.extend(3u32.to_le_bytes());
+        buf.extend(b"appinfo\x00config\x00installdir\x00");
+
+        let mut appinfo = crate::binary::AppInfo::new(std::io::Cursor::new(buf)).unwrap();
+        assert_eq!(appinfo.apps().unwrap().len(), 2);
+
+        let app = appinfo.find(489830).unwrap().unwrap();
+        assert_eq!(app.change_number, 42);
+        assert_eq!(app.data.get_path("appinfo/config/installdir").and_then(crate::Vdf::as_str), Some("Skyrim Special Edition"));
+        assert!(appinfo.find(1).unwrap().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use std::collections::HashMap;
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, Deserialize)]
+        struct LibraryFolders {
+            libraryfolders: HashMap<String, LibraryFolder>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct LibraryFolder {
+            path: PathBuf,
+            apps: HashMap<u32, u64>,
+        }
+
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct AppManifest {
+            appid: u32,
+            installdir: String,
+            buildid: u64,
+            state_flags: u32,
+            #[serde(default)]
+            auto_update: bool,
+            name: Option<String>,
+        }
+
+        let folders = crate::serde::from_str::<LibraryFolders>("\
+\"libraryfolders\"
+{
+\t\"0\"
+\t{
+\t\t\"path\"\t\t\"/home/user/.local/share/Steam\"
+\t\t\"apps\"
+\t\t{
+\t\t\t\"489830\"\t\t\"12345\"
+\t\t}
+\t}
+}
+").unwrap();
+        let folder = &folders.libraryfolders["0"];
+        assert_eq!(folder.path, PathBuf::from("/home/user/.local/share/Steam"));
+        assert_eq!(folder.apps[&489830], 12345);
+
+        let text = "\
+\"AppState\"
+{
+\t\"appid\"\t\t\"489830\"
+\t\"installdir\"\t\t\"Skyrim Special Edition\"
+\t\"buildid\"\t\t\"14556021\"
+\t\"StateFlags\"\t\t\"4\"
+\t\"AutoUpdate\"\t\t\"1\"
+}
+";
+        let root = crate::parse_tree(&mut text.as_bytes()).unwrap();
+        let manifest = crate::serde::from_vdf::<AppManifest>(root.get("AppState").unwrap()).unwrap();
+        assert_eq!(manifest, AppManifest {
+            appid: 489830,
+            installdir: "Skyrim Special Edition".to_owned(),
+            buildid: 14556021,
+            state_flags: 4,
+            auto_update: true,
+            name: None,
+        });
+
+        let object = crate::serde::to_object(&manifest).unwrap();
+        assert_eq!(object.get("state_flags").and_then(crate::Vdf::as_str), Some("4"));
+        assert_eq!(object.get("auto_update").and_then(crate::Vdf::as_str), Some("1"));
+        assert!(!object.contains_key("name"));
+        assert_eq!(crate::serde::from_object::<AppManifest>(&object).unwrap(), manifest);
+
+        let error = crate::serde::from_str::<HashMap<String, LibraryFolder>>("\"0\" { \"path\" \"/\" \"apps\" { \"x\" \"1\" } }");
+        assert!(error.is_err());
+    }
 }
 
-use std::{collections::HashMap, io::Result, path::PathBuf};
+mod tree;
+pub use tree::{Object, Vdf};
+mod error;
+pub use error::VdfError;
+mod tokenizer;
+pub use tokenizer::{unescape, Token, Tokenizer};
+mod parser;
+pub use parser::{Mode, Options};
+mod writer;
+pub use writer::{to_string, write, Format};
+pub mod binary;
+#[cfg(feature = "serde")]
+pub mod serde;
+
+/// How deeply objects may nest, deeper documents are rejected rather than overflowing the stack
+const MAX_DEPTH: usize = 256;
+
+use std::{collections::HashMap, path::{Path, PathBuf}};
 
 /// Parse a buffer.
 /// Returns a key / value hashmap.
-pub fn parse<T: std::io::Read>(buf: &mut T) -> Result<HashMap<PathBuf, String>> {
+///
+/// This is a flattened view of [`parse_tree`], where keys are `/` separated paths.
+///
+/// # Errors
+/// Errors when the buffer cannot be read
+pub fn parse<T: std::io::Read>(buf: &mut T) -> Result<HashMap<PathBuf, String>, VdfError> {
+    Ok(parse_tree(buf)?.flatten())
+}
+
+/// Parse a buffer into a document tree, skipping over anything malformed.
+/// Returns the root object, which holds the top level keys.
+///
+/// # Errors
+/// Errors when the buffer cannot be read
+pub fn parse_tree<T: std::io::Read>(buf: &mut T) -> Result<Object, VdfError> {
+    parse_tree_with(buf, &Options::default())
+}
+
+/// Parse a buffer into a document tree.
+/// Returns the root object, which holds the top level keys.
+///
+/// # Errors
+/// Errors when the buffer cannot be read, or in strict mode when it is malformed
+pub fn parse_tree_with<T: std::io::Read>(buf: &mut T, options: &Options) -> Result<Object, VdfError> {
     let mut string = String::new();
     buf.read_to_string(&mut string)?;
 
-    let mut in_quotes = false;
-    let mut escape = false;
-    let mut current_key = String::new();
-    let mut current_value = String::new();
-    let mut path = PathBuf::new();
-    let mut kv_pairs = HashMap::<_, _>::new();
-    for char in string.chars() {
-        match char {
-            '\"' if !escape => {
-                if in_quotes {
-                    if current_key.is_empty() {
-                        std::mem::swap(&mut current_key, &mut current_value);
-                    } else {
-                        kv_pairs.insert(
-                            path.join(std::mem::take(&mut current_key)),
-                            std::mem::take(&mut current_value),
-                        );
-                    }
-                }
-
-                in_quotes = !in_quotes;
-            }
-            '{' if !in_quotes => {
-                path.push(&current_key);
-                current_key.clear();
-            }
-            '}' if !in_quotes => {
-                path.pop();
-            }
-            char => {
-                if escape {
-                    match char {
-                        'n' => current_value.push('\n'),
-                        't' => current_value.push('\t'),
-                        '\\' => current_value.push('\\'),
-                        '\"' => current_value.push('\"'),
-                        _ => (),
-                    }
-
-                    escape = false;
-                    continue;
-                } else if char == '\\' {
-                    escape = true;
-                    continue;
-                }
-
-                if in_quotes {
-                    current_value.push(char);
-                }
-            }
-        }
-    }
+    parse_str(&string, options)
+}
+
+/// Parse text into a document tree.
+/// Returns the root object, which holds the top level keys.
+///
+/// # Errors
+/// Errors in strict mode when the text is malformed, or when an included file cannot be read
+pub fn parse_str(text: &str, options: &Options) -> Result<Object, VdfError> {
+    parser::parse(text, options, None)
+}
+
+/// Parse a file into a document tree.
+/// `#include` and `#base` directives are resolved relative to the file.
+///
+/// # Errors
+/// Errors when the file or an included file cannot be read, or in strict mode when one is malformed
+pub fn parse_file<P: AsRef<Path>>(path: P, options: &Options) -> Result<Object, VdfError> {
+    let path = path.as_ref();
+    let string = std::fs::read_to_string(path)?;
 
-    Ok(kv_pairs)
+    let mut options = options.clone();
+    options.include_dir = path.parent().map(Path::to_path_buf);
+    parser::parse(&string, &options, Some(path))
 }
@@ -0,0 +1,239 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::{Object, Token, Tokenizer, Vdf, VdfError, MAX_DEPTH};
+
+/// How malformed input is handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Skip anything that does not make sense and return what could be read
+    #[default]
+    Lenient,
+    /// Reject malformed input with a [`VdfError::Syntax`]
+    Strict,
+}
+
+/// Options for parsing text documents
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub mode: Mode,
+    /// Platforms that `[$PLATFORM]` conditionals are true for, eg. `WIN32` or `LINUX`, by default the
+    /// platform this is running on
+    ///
+    /// Entries whose conditional is false are left out of the document.
+    pub platforms: Vec<String>,
+    /// Directory that `#include` and `#base` paths are relative to
+    ///
+    /// [`crate::parse_file`] sets this to the directory of the file being parsed. When it is not
+    /// set paths are relative to the current directory.
+    pub include_dir: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { mode: Mode::default(), platforms: host_platforms(), include_dir: None }
+    }
+}
+
+impl Options {
+    #[must_use]
+    pub fn strict() -> Self {
+        Self { mode: Mode::Strict, ..Self::default() }
+    }
+
+    #[must_use]
+    pub fn with_platforms<I: IntoIterator<Item = S>, S: Into<String>>(mut self, platforms: I) -> Self {
+        self.platforms = platforms.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// The names Valve uses for the platform this is running on
+fn host_platforms() -> Vec<String> {
+    let platforms: &[&str] = if cfg!(windows) {
+        &["WIN32", "WINDOWS"]
+    } else if cfg!(target_os = "linux") {
+        &["LINUX", "POSIX"]
+    } else if cfg!(target_os = "macos") {
+        &["OSX", "POSIX"]
+    } else if cfg!(unix) {
+        &["POSIX"]
+    } else {
+        &[]
+    };
+
+    platforms.iter().map(|&platform| platform.to_owned()).collect()
+}
+
+enum Directive {
+    Include(String),
+    Base(String),
+}
+
+/// An object being read and whether its conditional allows it into its parent
+struct Frame {
+    key: String,
+    object: Object,
+    keep: bool,
+}
+
+/// Evaluate a conditional such as `$WIN32 || !$X360` against the platforms
+///
+/// Returns `None` when the expression is malformed.
+fn evaluate(expression: &str, platforms: &[String]) -> Option<bool> {
+    let mut result = false;
+    for any in expression.split("||") {
+        let mut all = true;
+        for term in any.split("&&") {
+            let term = term.trim();
+            let (negate, term) = match term.strip_prefix('!') {
+                Some(term) => (true, term.trim_start()),
+                None => (false, term),
+            };
+
+            let platform = term.strip_prefix('$')?;
+            if platform.is_empty() || !platform.chars().all(|char| char.is_alphanumeric() || char == '_') {
+                return None;
+            }
+
+            let matches = platforms.iter().any(|p| p.eq_ignore_ascii_case(platform));
+            all &= matches != negate;
+        }
+        result |= all;
+    }
+
+    Some(result)
+}
+
+/// Build a tree from the tokens of a document, evaluating its conditionals
+fn build(text: &str, options: &Options) -> Result<(Object, Vec<Directive>), VdfError> {
+    let strict = options.mode == Mode::Strict;
+    let mut tokens = Tokenizer::new(text, options.mode);
+    let mut root = Object::new();
+    let mut stack = Vec::<Frame>::new();
+    let mut directives = Vec::new();
+    let mut key = None;
+    // The conditional given between a key and its value
+    let mut condition = None;
+    // Whether the last token completed an entry that a conditional may follow
+    let mut can_condition = false;
+    while let Some(token) = tokens.next() {
+        match token? {
+            Token::Key(string) => {
+                can_condition = false;
+                key = Some(string.into_owned());
+            }
+            Token::Value(string) => {
+                can_condition = false;
+                if let Some(key) = key.take() {
+                    if condition.take().unwrap_or(true) {
+                        stack.last_mut().map_or(&mut root, |frame| &mut frame.object).push(key, string.into_owned());
+                        can_condition = true;
+                    }
+                }
+            }
+            Token::ObjectStart => {
+                if stack.len() >= MAX_DEPTH {
+                    let (line, column) = tokens.position();
+                    return Err(VdfError::Syntax { line, column, expected: "fewer nested objects", found: "{".to_owned() });
+                }
+
+                can_condition = false;
+                let keep = condition.take().unwrap_or(true);
+                stack.push(Frame { key: key.take().unwrap_or_default(), object: Object::new(), keep });
+            }
+            Token::ObjectEnd => {
+                // A key without a value in lenient mode is dropped
+                can_condition = false;
+                condition = None;
+                key = None;
+
+                if let Some(frame) = stack.pop() {
+                    if frame.keep {
+                        stack.last_mut().map_or(&mut root, |parent| &mut parent.object).push(frame.key, frame.object);
+                        can_condition = true;
+                    }
+                }
+            }
+            Token::Conditional(expression) => {
+                let (line, column) = tokens.position();
+                let error = |expected| VdfError::Syntax { line, column, expected, found: format!("[{expression}]") };
+                let result = match evaluate(expression, &options.platforms) {
+                    Some(result) => result,
+                    None if strict => return Err(error("conditional such as [$WIN32]")),
+                    None => true,
+                };
+
+                if key.is_some() {
+                    condition = Some(result);
+                } else if can_condition {
+                    if !result {
+                        stack.last_mut().map_or(&mut root, |frame| &mut frame.object).pop();
+                    }
+                } else if strict {
+                    return Err(error("key"));
+                }
+                can_condition = false;
+            }
+            Token::Include(path) => {
+                can_condition = false;
+                directives.push(Directive::Include(path.into_owned()));
+            }
+            Token::Base(path) => {
+                can_condition = false;
+                directives.push(Directive::Base(path.into_owned()));
+            }
+        }
+    }
+
+    Ok((root, directives))
+}
+
+/// Add the entries of a `#base` file that the including file does not already have
+fn merge_base(object: &mut Object, base: Object) {
+    for (key, value) in base {
+        match (object.get_mut(&key), value) {
+            (Some(Vdf::Object(existing)), Vdf::Object(base)) => merge_base(existing, base),
+            (Some(_), _) => (),
+            (None, value) => object.push(key, value),
+        }
+    }
+}
+
+fn parse_nested(text: &str, options: &Options, dir: Option<&Path>, visiting: &mut Vec<PathBuf>) -> Result<Object, VdfError> {
+    let (mut root, directives) = build(text, options)?;
+
+    for directive in directives {
+        let (Directive::Include(file) | Directive::Base(file)) = &directive;
+        let path = dir.map_or_else(|| PathBuf::from(file), |dir| dir.join(file));
+        // Compared canonicalised so that eg. `a.vdf` and `sub/../a.vdf` are the same file
+        let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if visiting.contains(&canonical) {
+            return Err(VdfError::IncludeCycle(path));
+        }
+
+        let included = fs::read_to_string(&path)
+            .map_err(VdfError::from)
+            .and_then(|text| {
+                visiting.push(canonical);
+                let included = parse_nested(&text, options, path.parent(), visiting);
+                visiting.pop();
+                included
+            })
+            .map_err(|e| VdfError::Include { path, source: Box::new(e) })?;
+
+        match directive {
+            Directive::Include(_) => root.extend(included),
+            Directive::Base(_) => merge_base(&mut root, included),
+        }
+    }
+
+    Ok(root)
+}
+
+/// Parse a document and resolve its `#include` and `#base` directives
+///
+/// `file` is the path the text was read from, if any, so that it cannot include itself.
+pub(crate) fn parse(text: &str, options: &Options, file: Option<&Path>) -> Result<Object, VdfError> {
+    let mut visiting = file.into_iter().map(|file| fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf())).collect();
+    parse_nested(text, options, options.include_dir.as_deref(), &mut visiting)
+}
@@ -0,0 +1,366 @@
+use ::serde::de::{self, DeserializeSeed, IntoDeserializer, Unexpected, Visitor};
+use ::serde::forward_to_deserialize_any;
+use crate::{Object, Vdf};
+use super::{Error, Result};
+
+/// Deserializer over a node of a parsed document
+pub struct Deserializer<'de> {
+    node: Node<'de>,
+}
+
+#[derive(Clone, Copy)]
+enum Node<'de> {
+    Vdf(&'de Vdf),
+    Object(&'de Object),
+}
+
+impl<'de> Deserializer<'de> {
+    #[must_use]
+    pub fn new(node: &'de Vdf) -> Self {
+        match node {
+            Vdf::Object(object) => Self::from_object(object),
+            node => Self { node: Node::Vdf(node) },
+        }
+    }
+
+    #[must_use]
+    pub fn from_object(object: &'de Object) -> Self {
+        Self { node: Node::Object(object) }
+    }
+
+    fn unexpected(&self) -> Unexpected<'de> {
+        match self.node {
+            Node::Object(_) => Unexpected::Map,
+            Node::Vdf(Vdf::Value(value)) => Unexpected::Str(value),
+            Node::Vdf(Vdf::Float(float)) => Unexpected::Float(f64::from(*float)),
+            Node::Vdf(node) => node.as_i64().map_or(Unexpected::Other("number"), Unexpected::Signed),
+        }
+    }
+}
+
+/// Whether a key matches a struct field, ignoring case and underscores
+fn field_matches(field: &str, key: &str) -> bool {
+    let mut field = field.chars().filter(|c| *c != '_');
+    let mut key = key.chars().filter(|c| *c != '_');
+    loop {
+        match (field.next(), key.next()) {
+            (Some(a), Some(b)) if a.eq_ignore_ascii_case(&b) => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                match self.node {
+                    Node::Vdf(Vdf::Value(value)) => StrDeserializer(value).$method(visitor),
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.node {
+            Node::Object(object) => visitor.visit_map(ObjectAccess::new(object, None)),
+            Node::Vdf(node) => match node {
+                Vdf::Value(value) => visitor.visit_borrowed_str(value),
+                Vdf::Object(object) => visitor.visit_map(ObjectAccess::new(object, None)),
+                Vdf::Int(int) | Vdf::Pointer(int) => visitor.visit_i32(*int),
+                Vdf::Float(float) => visitor.visit_f32(*float),
+                Vdf::Color(color) => visitor.visit_u32(*color),
+                Vdf::UInt64(int) => visitor.visit_u64(*int),
+                Vdf::Int64(int) => visitor.visit_i64(*int),
+            },
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.node {
+            Node::Vdf(Vdf::Value(value)) => StrDeserializer(value).deserialize_bool(visitor),
+            Node::Vdf(node) if node.is_value() => match node.as_i64() {
+                Some(int) => visitor.visit_bool(int != 0),
+                None => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+            },
+            _ => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_i128,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_u128,
+        deserialize_f32,
+        deserialize_f64,
+        deserialize_char
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.node {
+            Node::Vdf(Vdf::Value(value)) => visitor.visit_borrowed_str(value),
+            Node::Vdf(node) if node.is_value() => visitor.visit_string(node.to_value_string().unwrap_or_default()),
+            _ => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.node {
+            Node::Vdf(Vdf::Value(value)) => visitor.visit_borrowed_bytes(value.as_bytes()),
+            _ => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.node {
+            Node::Object(object) | Node::Vdf(Vdf::Object(object)) => {
+                visitor.visit_seq(SeqAccess { iter: object.entries().iter() })
+            }
+            Node::Vdf(_) => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.node {
+            Node::Object(object) | Node::Vdf(Vdf::Object(object)) => visitor.visit_map(ObjectAccess::new(object, None)),
+            Node::Vdf(_) => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        match self.node {
+            Node::Object(object) | Node::Vdf(Vdf::Object(object)) => visitor.visit_map(ObjectAccess::new(object, Some(fields))),
+            Node::Vdf(_) => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        match self.node {
+            Node::Vdf(Vdf::Value(value)) => visitor.visit_enum(value.as_str().into_deserializer()),
+            Node::Object(object) | Node::Vdf(Vdf::Object(object)) if object.len() == 1 => {
+                let (key, value) = &object.entries()[0];
+                visitor.visit_enum(EnumAccess { key, value })
+            }
+            _ => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+/// Deserializer for a string value, parsing numbers and bools
+struct StrDeserializer<'de>(&'de str);
+
+macro_rules! parse_number {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                match self.0.trim().parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::invalid_value(Unexpected::Str(self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for StrDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0.trim() {
+            "1" => visitor.visit_bool(true),
+            "0" | "" => visitor.visit_bool(false),
+            s if s.eq_ignore_ascii_case("true") => visitor.visit_bool(true),
+            s if s.eq_ignore_ascii_case("false") => visitor.visit_bool(false),
+            _ => Err(de::Error::invalid_value(Unexpected::Str(self.0), &visitor)),
+        }
+    }
+
+    parse_number! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+/// Access to the children of an object as a map
+struct ObjectAccess<'de> {
+    iter: std::slice::Iter<'de, (String, Vdf)>,
+    value: Option<&'de Vdf>,
+    fields: Option<&'static [&'static str]>,
+}
+
+impl<'de> ObjectAccess<'de> {
+    fn new(object: &'de Object, fields: Option<&'static [&'static str]>) -> Self {
+        Self { iter: object.entries().iter(), value: None, fields }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for ObjectAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let Some((key, value)) = self.iter.next() else {
+            return Ok(None)
+        };
+
+        self.value = Some(value);
+
+        let field = self.fields
+            .and_then(|fields| fields.iter().find(|field| field_matches(field, key)));
+        match field {
+            Some(field) => seed.deserialize(StrDeserializer(field)).map(Some),
+            None => seed.deserialize(StrDeserializer(key)).map(Some),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self.value.take().ok_or_else(|| Error::Message("value requested before key".to_owned()))?;
+        seed.deserialize(Deserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Access to the children of an object as a sequence, ignoring their keys
+struct SeqAccess<'de> {
+    iter: std::slice::Iter<'de, (String, Vdf)>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.iter.next() {
+            Some((_, value)) => seed.deserialize(Deserializer::new(value)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// An enum variant written as an object with a single key
+struct EnumAccess<'de> {
+    key: &'de str,
+    value: &'de Vdf,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant)> {
+        Ok((seed.deserialize(StrDeserializer(self.key))?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(Deserializer::new(self.value))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(Deserializer::new(self.value), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_struct(Deserializer::new(self.value), "", fields, visitor)
+    }
+}
@@ -0,0 +1,96 @@
+//! Serde support, enabled with the `serde` feature
+//!
+//! VDF only has strings and objects, so numbers and bools are parsed from strings when
+//! deserializing and written as strings when serializing. Sequences are objects keyed
+//! `"0"`, `"1"`, ... as Steam writes them.
+//!
+//! Struct fields are matched ignoring case and underscores, so `state_flags` reads `StateFlags`.
+
+mod de;
+mod ser;
+
+use std::fmt::Display;
+use std::io::Read;
+use ::serde::de::DeserializeOwned;
+use ::serde::{Deserialize, Serialize};
+use crate::{Format, Object, Options, Vdf, VdfError};
+
+pub use de::Deserializer;
+pub use ser::Serializer;
+
+/// Errors from serializing or deserializing
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Message(String),
+
+    #[error(transparent)]
+    Parse(#[from] VdfError),
+}
+
+impl ::serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+impl ::serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Deserialize a value from a parsed document
+///
+/// # Errors
+/// Errors when the document does not match the type
+pub fn from_object<'de, T: Deserialize<'de>>(root: &'de Object) -> Result<T> {
+    T::deserialize(Deserializer::from_object(root))
+}
+
+/// Deserialize a value from a node of a parsed document
+///
+/// # Errors
+/// Errors when the node does not match the type
+pub fn from_vdf<'de, T: Deserialize<'de>>(node: &'de Vdf) -> Result<T> {
+    T::deserialize(Deserializer::new(node))
+}
+
+/// Deserialize a value from VDF text, malformed text is rejected
+///
+/// # Errors
+/// Errors when the text cannot be parsed or does not match the type
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T> {
+    let root = crate::parse_str(s, &Options::strict())?;
+    from_object(&root)
+}
+
+/// Deserialize a value from a VDF text buffer, malformed text is rejected
+///
+/// # Errors
+/// Errors when the buffer cannot be read or parsed, or does not match the type
+pub fn from_reader<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<T> {
+    let root = crate::parse_tree_with(reader, &Options::strict())?;
+    from_object(&root)
+}
+
+/// Serialize a struct or map to a document
+///
+/// # Errors
+/// Errors when the value is not a struct or map, or a map key is not a string or number
+pub fn to_object<T: Serialize + ?Sized>(value: &T) -> Result<Object> {
+    match value.serialize(Serializer)? {
+        Some(Vdf::Object(object)) => Ok(object),
+        _ => Err(Error::Message("only structs and maps can be serialized as a document".to_owned())),
+    }
+}
+
+/// Serialize a struct or map to VDF text in the default format
+///
+/// # Errors
+/// Errors when the value is not a struct or map, or a map key is not a string or number
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    Ok(crate::to_string(&to_object(value)?, &Format::default()))
+}
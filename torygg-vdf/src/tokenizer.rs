@@ -0,0 +1,337 @@
+use std::borrow::Cow;
+use crate::{Mode, VdfError};
+
+/// A structural event in a text document
+///
+/// Strings borrow from the text being tokenized, they are only copied when they contain
+/// escape sequences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token<'a> {
+    Key(Cow<'a, str>),
+    Value(Cow<'a, str>),
+    /// The start of the object belonging to the previous key
+    ObjectStart,
+    ObjectEnd,
+    /// A conditional such as `$WIN32`, without its brackets
+    ///
+    /// It applies to the entry before it, or the entry being read when it follows a key.
+    Conditional(&'a str),
+    /// An `#include` directive and the path it includes
+    Include(Cow<'a, str>),
+    /// A `#base` directive and the path it includes
+    Base(Cow<'a, str>),
+}
+
+enum Lexeme<'a> {
+    String(Cow<'a, str>),
+    ObjectStart,
+    ObjectEnd,
+    Conditional(&'a str),
+    Eof,
+}
+
+/// Streaming tokenizer for text documents
+///
+/// Keys and values are told apart as the document is read, and in strict mode the structure is
+/// checked as it goes. In lenient mode any objects left open at the end of the text are closed
+/// with [`Token::ObjectEnd`], so the stream is always balanced.
+pub struct Tokenizer<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+    column: usize,
+    token_line: usize,
+    token_column: usize,
+    strict: bool,
+    depth: usize,
+    after_key: bool,
+    pending: Option<Token<'a>>,
+    done: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    #[must_use]
+    pub fn new(text: &'a str, mode: Mode) -> Self {
+        Self {
+            text,
+            pos: 0,
+            line: 1,
+            column: 1,
+            token_line: 1,
+            token_column: 1,
+            strict: mode == Mode::Strict,
+            depth: 0,
+            after_key: false,
+            pending: None,
+            done: false,
+        }
+    }
+
+    /// The line and column the last token started at, both starting at 1
+    #[must_use]
+    pub fn position(&self) -> (usize, usize) {
+        (self.token_line, self.token_column)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.pos += char.len_utf8();
+        if char == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(char)
+    }
+
+    fn error(&self, expected: &'static str, found: &str) -> VdfError {
+        VdfError::Syntax { line: self.token_line, column: self.token_column, expected, found: found.to_owned() }
+    }
+
+    /// Skip whitespace and `//` comments
+    fn skip_trivia(&mut self) {
+        loop {
+            if self.rest().starts_with("//") {
+                while self.peek().is_some_and(|char| char != '\n') {
+                    self.next_char();
+                }
+            } else if self.peek().is_some_and(char::is_whitespace) {
+                self.next_char();
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Read a quoted string, the opening quote has already been consumed
+    ///
+    /// Returns `None` for an unterminated string in lenient mode.
+    fn quoted(&mut self) -> Result<Option<Cow<'a, str>>, VdfError> {
+        let start = self.pos;
+        let mut escaped = false;
+        loop {
+            let Some(char) = self.next_char() else {
+                return if self.strict {
+                    Err(self.error("closing '\"'", "end of input"))
+                } else {
+                    Ok(None)
+                };
+            };
+
+            match char {
+                '"' => break,
+                '\\' => {
+                    escaped = true;
+                    // Where the backslash is, before a following newline moves the position
+                    let (line, column) = (self.line, self.column - 1);
+                    match self.next_char() {
+                        Some('n' | 't' | '\\' | '"') | None => (),
+                        Some(char) if self.strict => {
+                            return Err(VdfError::Syntax { line, column, expected: "escape sequence", found: format!("'\\{char}'") });
+                        }
+                        Some(_) => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let raw = &self.text[start..self.pos - 1];
+        Ok(Some(if escaped { Cow::Owned(unescape(raw).into_owned()) } else { Cow::Borrowed(raw) }))
+    }
+
+    /// Read an unquoted string, which ends at whitespace, a quote, a brace, a conditional or a comment
+    fn unquoted(&mut self) -> &'a str {
+        let start = self.pos;
+        while let Some(char) = self.peek() {
+            if char.is_whitespace() || matches!(char, '"' | '{' | '}' | '[') || self.rest().starts_with("//") {
+                break;
+            }
+            self.next_char();
+        }
+
+        &self.text[start..self.pos]
+    }
+
+    /// Read a conditional, the opening bracket has already been consumed
+    ///
+    /// Returns `None` for an unterminated conditional in lenient mode.
+    fn conditional(&mut self) -> Result<Option<&'a str>, VdfError> {
+        let start = self.pos;
+        loop {
+            match self.next_char() {
+                Some(']') => return Ok(Some(&self.text[start..self.pos - 1])),
+                Some('\n') | None if self.strict => return Err(self.error("closing ']'", "end of line")),
+                None => return Ok(None),
+                Some(_) => (),
+            }
+        }
+    }
+
+    fn lex(&mut self) -> Result<Lexeme<'a>, VdfError> {
+        self.skip_trivia();
+
+        (self.token_line, self.token_column) = (self.line, self.column);
+        let Some(char) = self.peek() else {
+            return Ok(Lexeme::Eof);
+        };
+
+        let lexeme = match char {
+            '"' => {
+                self.next_char();
+                match self.quoted()? {
+                    Some(string) => Lexeme::String(string),
+                    None => Lexeme::Eof,
+                }
+            }
+            '[' => {
+                self.next_char();
+                match self.conditional()? {
+                    Some(expression) => Lexeme::Conditional(expression),
+                    None => Lexeme::Eof,
+                }
+            }
+            '{' => {
+                self.next_char();
+                Lexeme::ObjectStart
+            }
+            '}' => {
+                self.next_char();
+                Lexeme::ObjectEnd
+            }
+            _ => Lexeme::String(Cow::Borrowed(self.unquoted())),
+        };
+
+        Ok(lexeme)
+    }
+
+    fn token(&mut self) -> Result<Option<Token<'a>>, VdfError> {
+        if let Some(token) = self.pending.take() {
+            return Ok(Some(token));
+        }
+
+        loop {
+            match self.lex()? {
+                Lexeme::String(string) if self.after_key => {
+                    self.after_key = false;
+                    return Ok(Some(Token::Value(string)));
+                }
+                Lexeme::String(string) if self.depth == 0 && is_directive(&string) => {
+                    let include = string.eq_ignore_ascii_case("#include");
+                    match self.lex()? {
+                        Lexeme::String(path) if include => return Ok(Some(Token::Include(path))),
+                        Lexeme::String(path) => return Ok(Some(Token::Base(path))),
+                        _ if self.strict => return Err(self.error("file path", "end of directive")),
+                        _ => (),
+                    }
+                }
+                Lexeme::String(string) => {
+                    self.after_key = true;
+                    return Ok(Some(Token::Key(string)));
+                }
+                Lexeme::ObjectStart => {
+                    self.depth += 1;
+                    if self.after_key {
+                        self.after_key = false;
+                        return Ok(Some(Token::ObjectStart));
+                    } else if self.strict {
+                        return Err(self.error("key", "'{'"));
+                    }
+
+                    self.pending = Some(Token::ObjectStart);
+                    return Ok(Some(Token::Key(Cow::Borrowed(""))));
+                }
+                Lexeme::ObjectEnd => {
+                    if self.after_key && self.strict {
+                        return Err(self.error("value or '{'", "'}'"));
+                    }
+                    self.after_key = false;
+
+                    if self.depth > 0 {
+                        self.depth -= 1;
+                        return Ok(Some(Token::ObjectEnd));
+                    } else if self.strict {
+                        return Err(self.error("key", "'}'"));
+                    }
+                }
+                Lexeme::Conditional(expression) => return Ok(Some(Token::Conditional(expression))),
+                Lexeme::Eof => {
+                    if self.strict {
+                        if self.after_key {
+                            return Err(self.error("value or '{'", "end of input"));
+                        } else if self.depth > 0 {
+                            return Err(self.error("'}'", "end of input"));
+                        }
+                    }
+
+                    // Close any objects left open at the end of the text
+                    self.after_key = false;
+                    if self.depth > 0 {
+                        self.depth -= 1;
+                        return Ok(Some(Token::ObjectEnd));
+                    }
+
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, VdfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let token = self.token();
+        if !matches!(token, Ok(Some(_))) {
+            self.done = true;
+        }
+        token.transpose()
+    }
+}
+
+fn is_directive(string: &str) -> bool {
+    string.eq_ignore_ascii_case("#include") || string.eq_ignore_ascii_case("#base")
+}
+
+/// Replace the escape sequences `\n`, `\t`, `\\` and `\"` in a quoted string
+///
+/// Unknown escape sequences are dropped. Only allocates when there is something to replace.
+#[must_use]
+pub fn unescape(raw: &str) -> Cow<'_, str> {
+    if !raw.contains('\\') {
+        return Cow::Borrowed(raw);
+    }
+
+    let mut string = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            string.push(char);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => string.push('\n'),
+            Some('t') => string.push('\t'),
+            Some('\\') => string.push('\\'),
+            Some('"') => string.push('"'),
+            _ => (),
+        }
+    }
+
+    Cow::Owned(string)
+}
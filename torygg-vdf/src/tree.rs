@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A node in a VDF document, either a string value or a nested object
+///
+/// Text documents only contain strings and objects, the typed numbers are read from binary
+/// documents and are kept so that they can be written back out with the same type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vdf {
+    Value(String),
+    Object(Object),
+    Int(i32),
+    Float(f32),
+    Pointer(i32),
+    Color(u32),
+    UInt64(u64),
+    Int64(i64),
+}
+
+impl Vdf {
+    /// Whether this node is anything other than an object
+    #[must_use]
+    pub fn is_value(&self) -> bool {
+        !self.is_object()
+    }
+
+    #[must_use]
+    pub fn is_object(&self) -> bool {
+        matches!(self, Self::Object(_))
+    }
+
+    /// The string held by this node, if it is a string value
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The integer held by this node, parsing string values
+    #[must_use]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Value(value) => value.trim().parse().ok(),
+            Self::Int(int) | Self::Pointer(int) => Some(i64::from(*int)),
+            Self::Color(color) => Some(i64::from(*color)),
+            Self::UInt64(int) => i64::try_from(*int).ok(),
+            Self::Int64(int) => Some(*int),
+            Self::Object(_) | Self::Float(_) => None,
+        }
+    }
+
+    /// The value as text, numbers are formatted as they would be in a text document
+    #[must_use]
+    pub fn to_value_string(&self) -> Option<String> {
+        match self {
+            Self::Value(value) => Some(value.clone()),
+            Self::Object(_) => None,
+            Self::Int(int) | Self::Pointer(int) => Some(int.to_string()),
+            Self::Float(float) => Some(float.to_string()),
+            Self::Color(color) => Some(color.to_string()),
+            Self::UInt64(int) => Some(int.to_string()),
+            Self::Int64(int) => Some(int.to_string()),
+        }
+    }
+
+    /// The object held by this node, if it is an object
+    #[must_use]
+    pub fn as_object(&self) -> Option<&Object> {
+        match self {
+            Self::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_object_mut(&mut self) -> Option<&mut Object> {
+        match self {
+            Self::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    /// Get the first child with the given key, if this node is an object
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Vdf> {
+        self.as_object()?.get(key)
+    }
+
+    /// Look up a node by a `/` separated path, eg. `libraryfolders/0/path`
+    #[must_use]
+    pub fn get_path<P: AsRef<Path>>(&self, path: P) -> Option<&Vdf> {
+        self.as_object()?.get_path(path)
+    }
+}
+
+impl From<String> for Vdf {
+    fn from(value: String) -> Self {
+        Self::Value(value)
+    }
+}
+
+impl From<&str> for Vdf {
+    fn from(value: &str) -> Self {
+        Self::Value(value.to_owned())
+    }
+}
+
+impl From<Object> for Vdf {
+    fn from(object: Object) -> Self {
+        Self::Object(object)
+    }
+}
+
+/// An ordered list of key / node pairs
+///
+/// Keys are matched case-insensitively, as Steam does, and duplicate keys are kept in the order
+/// they were read.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Object {
+    entries: Vec<(String, Vdf)>,
+}
+
+impl Object {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn entries(&self) -> &[(String, Vdf)] {
+        &self.entries
+    }
+
+    /// Iterate over all children in document order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Vdf)> {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut Vdf)> {
+        self.entries.iter_mut().map(|(key, value)| (key.as_str(), value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(key, _)| key.as_str())
+    }
+
+    /// Get the first child with the given key
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Vdf> {
+        self.entries.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    #[must_use]
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Vdf> {
+        self.entries.iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Get every child with the given key
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a Vdf> {
+        self.entries.iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    #[must_use]
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Look up a node by a `/` separated path, eg. `libraryfolders/0/path`
+    ///
+    /// Each component selects the first child with that key.
+    #[must_use]
+    pub fn get_path<P: AsRef<Path>>(&self, path: P) -> Option<&Vdf> {
+        let mut components = path.as_ref().iter();
+        let mut node = self.get(&components.next()?.to_string_lossy())?;
+        for component in components {
+            node = node.get(&component.to_string_lossy())?;
+        }
+
+        Some(node)
+    }
+
+    #[must_use]
+    pub fn get_path_mut<P: AsRef<Path>>(&mut self, path: P) -> Option<&mut Vdf> {
+        let mut components = path.as_ref().iter();
+        let mut node = self.get_mut(&components.next()?.to_string_lossy())?;
+        for component in components {
+            node = node.as_object_mut()?.get_mut(&component.to_string_lossy())?;
+        }
+
+        Some(node)
+    }
+
+    /// Append a child, keeping any existing children with the same key
+    pub fn push<K: Into<String>, V: Into<Vdf>>(&mut self, key: K, value: V) {
+        self.entries.push((key.into(), value.into()));
+    }
+
+    /// Remove the last child
+    pub fn pop(&mut self) -> Option<(String, Vdf)> {
+        self.entries.pop()
+    }
+
+    /// Replace the first child with the given key, or append it if there is none
+    ///
+    /// Returns the replaced node.
+    pub fn insert<K: Into<String>, V: Into<Vdf>>(&mut self, key: K, value: V) -> Option<Vdf> {
+        let key = key.into();
+        match self.get_mut(&key) {
+            Some(existing) => Some(std::mem::replace(existing, value.into())),
+            None => {
+                self.push(key, value);
+                None
+            }
+        }
+    }
+
+    /// Remove the first child with the given key
+    pub fn remove(&mut self, key: &str) -> Option<Vdf> {
+        let index = self.entries.iter().position(|(k, _)| k.eq_ignore_ascii_case(key))?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// Flatten the document into a map of `/` separated paths to values
+    ///
+    /// Objects do not get an entry of their own. Where keys are duplicated the last value wins.
+    #[must_use]
+    pub fn flatten(&self) -> HashMap<PathBuf, String> {
+        let mut map = HashMap::new();
+        self.flatten_into(&PathBuf::new(), &mut map);
+        map
+    }
+
+    fn flatten_into(&self, path: &Path, map: &mut HashMap<PathBuf, String>) {
+        for (key, value) in &self.entries {
+            let path = path.join(key);
+            if let Vdf::Object(object) = value {
+                object.flatten_into(&path, map);
+            } else if let Some(value) = value.to_value_string() {
+                map.insert(path, value);
+            }
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<Vdf>> FromIterator<(K, V)> for Object {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self {
+            entries: iter.into_iter().map(|(key, value)| (key.into(), value.into())).collect()
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<Vdf>> Extend<(K, V)> for Object {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        self.entries.extend(iter.into_iter().map(|(key, value)| (key.into(), value.into())));
+    }
+}
+
+impl IntoIterator for Object {
+    type Item = (String, Vdf);
+    type IntoIter = std::vec::IntoIter<(String, Vdf)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Object {
+    type Item = (&'a str, &'a Vdf);
+    type IntoIter = std::iter::Map<std::slice::Iter<'a, (String, Vdf)>, fn(&'a (String, Vdf)) -> (&'a str, &'a Vdf)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value))
+    }
+}
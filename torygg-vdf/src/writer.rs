@@ -0,0 +1,145 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Result, Write};
+use crate::{Object, Vdf};
+
+/// Layout used when writing a document
+///
+/// The default matches the files Steam writes. Use [`Format::detect`] to keep the layout of an
+/// existing file when writing it back out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Format {
+    /// Whitespace for each level of nesting
+    pub indent: String,
+    /// Whitespace between a key and its value
+    pub separator: String,
+    /// Line ending
+    pub newline: String,
+    /// Write newlines and tabs in values as `\n` and `\t` rather than as is
+    pub escape_control: bool,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            indent: "\t".to_owned(),
+            separator: "\t\t".to_owned(),
+            newline: "\n".to_owned(),
+            escape_control: true,
+        }
+    }
+}
+
+impl Format {
+    /// Work out the layout of a document from its text
+    ///
+    /// Anything that cannot be determined falls back to the default.
+    #[must_use]
+    pub fn detect(text: &str) -> Self {
+        let mut format = Self::default();
+
+        if text.contains("\r\n") {
+            format.newline = "\r\n".to_owned();
+        }
+
+        let indent = text.lines()
+            .map(|line| &line[..line.len() - line.trim_start().len()])
+            .find(|indent| !indent.is_empty());
+        if let Some(indent) = indent {
+            indent.clone_into(&mut format.indent);
+        }
+
+        if let Some(separator) = text.lines().find_map(separator) {
+            separator.clone_into(&mut format.separator);
+        }
+
+        let mut chars = text.chars();
+        let mut escape_control = false;
+        while let Some(char) = chars.next() {
+            if char == '\\' && matches!(chars.next(), Some('n' | 't')) {
+                escape_control = true;
+                break;
+            }
+        }
+        format.escape_control = escape_control;
+
+        format
+    }
+}
+
+/// The whitespace between the key and value of a `"key" "value"` line
+fn separator(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('"')?;
+
+    let mut escape = false;
+    let key_end = rest.char_indices().find_map(|(i, char)| {
+        match char {
+            '"' if !escape => return Some(i + 1),
+            '\\' => escape = !escape,
+            _ => escape = false,
+        }
+        None
+    })?;
+
+    let rest = &rest[key_end..];
+    let value_start = rest.find(|char: char| !char.is_whitespace())?;
+    if value_start > 0 && rest[value_start..].starts_with('"') {
+        Some(&rest[..value_start])
+    } else {
+        None
+    }
+}
+
+fn write_quoted<W: Write>(writer: &mut W, string: &str, format: &Format) -> Result<()> {
+    writer.write_all(b"\"")?;
+    for char in string.chars() {
+        match char {
+            '"' => writer.write_all(b"\\\"")?,
+            '\\' => writer.write_all(b"\\\\")?,
+            '\n' if format.escape_control => writer.write_all(b"\\n")?,
+            '\t' if format.escape_control => writer.write_all(b"\\t")?,
+            char => write!(writer, "{char}")?,
+        }
+    }
+    writer.write_all(b"\"")
+}
+
+fn write_object<W: Write>(writer: &mut W, object: &Object, format: &Format, depth: usize) -> Result<()> {
+    let indent = format.indent.repeat(depth);
+    for (key, value) in object {
+        writer.write_all(indent.as_bytes())?;
+        write_quoted(writer, key, format)?;
+        if let Vdf::Object(object) = value {
+            write!(writer, "{newline}{indent}{{{newline}", newline = format.newline)?;
+            write_object(writer, object, format, depth + 1)?;
+            write!(writer, "{indent}}}{newline}", newline = format.newline)?;
+        } else if let Some(value) = value.to_value_string() {
+            writer.write_all(format.separator.as_bytes())?;
+            write_quoted(writer, &value, format)?;
+            writer.write_all(format.newline.as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write a document, as returned by [`crate::parse_tree`], to a writer
+///
+/// # Errors
+/// Errors when writing fails
+pub fn write<W: Write>(writer: &mut W, root: &Object, format: &Format) -> Result<()> {
+    write_object(writer, root, format, 0)
+}
+
+/// Write a document to a string
+#[must_use]
+pub fn to_string(root: &Object, format: &Format) -> String {
+    let mut buf = Vec::new();
+    write(&mut buf, root, format).expect("writing to a vec does not fail");
+    String::from_utf8(buf).expect("keys and values are valid utf-8")
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&to_string(self, &Format::default()))
+    }
+}
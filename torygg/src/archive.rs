@@ -0,0 +1,251 @@
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use log::{info, warn};
+use crate::error::ToryggError;
+use crate::settings::Settings;
+
+/// The kind of archive a file is, from its signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    SevenZip,
+    Rar,
+    /// Anything else, which only an external extractor may handle
+    Unknown,
+}
+
+impl ArchiveFormat {
+    /// Read the start of a file to tell its format
+    ///
+    /// # Errors
+    /// Errors when the file cannot be read
+    pub fn detect(path: &Path) -> Result<ArchiveFormat, ToryggError> {
+        let mut signature = Vec::with_capacity(8);
+        File::open(path)?.take(8).read_to_end(&mut signature)?;
+
+        Ok(if signature.starts_with(b"PK\x03\x04") || signature.starts_with(b"PK\x05\x06") {
+            ArchiveFormat::Zip
+        } else if signature.starts_with(b"7z\xbc\xaf\x27\x1c") {
+            ArchiveFormat::SevenZip
+        } else if signature.starts_with(b"Rar!\x1a\x07") {
+            ArchiveFormat::Rar
+        } else {
+            ArchiveFormat::Unknown
+        })
+    }
+}
+
+impl Display for ArchiveFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::SevenZip => "7z",
+            ArchiveFormat::Rar => "rar",
+            ArchiveFormat::Unknown => "unknown format",
+        })
+    }
+}
+
+/// Something that can extract archives of some formats
+pub trait ArchiveExtractor {
+    /// What the extractor is called in logs and errors
+    fn name(&self) -> String;
+
+    fn supports(&self, format: ArchiveFormat) -> bool;
+
+    /// Extract every file in `archive` into `dest`, the reason is returned when it fails
+    ///
+    /// # Errors
+    /// Errors when the archive cannot be read or a file cannot be written
+    fn extract(&self, archive: &Path, dest: &Path) -> Result<(), String>;
+}
+
+/// Extracts zip archives using deflate or lzma, without any external program
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZipExtractor;
+
+impl ArchiveExtractor for ZipExtractor {
+    fn name(&self) -> String {
+        "built in zip".to_owned()
+    }
+
+    fn supports(&self, format: ArchiveFormat) -> bool {
+        format == ArchiveFormat::Zip
+    }
+
+    fn extract(&self, archive: &Path, dest: &Path) -> Result<(), String> {
+        let file = File::open(archive).map_err(|e| e.to_string())?;
+        // Entries that would end up outside `dest` are refused
+        zip::ZipArchive::new(file)
+            .and_then(|mut zip| zip.extract(dest))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Extracts 7z archives without any external program
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SevenZipExtractor;
+
+impl ArchiveExtractor for SevenZipExtractor {
+    fn name(&self) -> String {
+        "built in 7z".to_owned()
+    }
+
+    fn supports(&self, format: ArchiveFormat) -> bool {
+        format == ArchiveFormat::SevenZip
+    }
+
+    fn extract(&self, archive: &Path, dest: &Path) -> Result<(), String> {
+        sevenz_rust::decompress_file_with_extract_fn(archive, dest, |entry, reader, _| {
+            let Some(relative_path) = enclosed_path(entry.name()) else {
+                return Err(sevenz_rust::Error::other(format!("{} is outside the archive", entry.name())));
+            };
+
+            let path = dest.join(relative_path);
+            if entry.is_directory() {
+                fs::create_dir_all(&path)?;
+            } else {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                io::copy(reader, &mut File::create(&path)?)?;
+            }
+
+            Ok(true)
+        }).map_err(|e| match e {
+            sevenz_rust::Error::PasswordRequired | sevenz_rust::Error::MaybeBadPassword(_) => "the archive is encrypted".to_owned(),
+            sevenz_rust::Error::UnsupportedCompressionMethod(method) if method.starts_with("AES") => "the archive is encrypted".to_owned(),
+            sevenz_rust::Error::UnsupportedCompressionMethod(method) => format!("unsupported compression method {method}"),
+            e => e.to_string(),
+        })
+    }
+}
+
+/// Extracts archives with an external `7z`, which handles rar and most other formats
+#[derive(Debug, Clone)]
+pub struct ExternalSevenZip {
+    program: PathBuf,
+}
+
+impl ExternalSevenZip {
+    #[must_use]
+    pub fn new(program: impl Into<PathBuf>) -> ExternalSevenZip {
+        ExternalSevenZip { program: program.into() }
+    }
+}
+
+impl ArchiveExtractor for ExternalSevenZip {
+    fn name(&self) -> String {
+        self.program.display().to_string()
+    }
+
+    fn supports(&self, _format: ArchiveFormat) -> bool {
+        true
+    }
+
+    fn extract(&self, archive: &Path, dest: &Path) -> Result<(), String> {
+        let output = Command::new(&self.program)
+            .arg("x")
+            // Never wait for a password on stdin
+            .arg("-p")
+            .arg(format!("-o{}", dest.display()))
+            .arg(archive)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => format!("{} is not installed, install p7zip or set seven_zip", self.program.display()),
+                _ => format!("failed to run {}: {e}", self.program.display()),
+            })?;
+
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join(" ");
+        Err(if message.is_empty() { format!("exited with {}", output.status) } else { message })
+    }
+}
+
+/// An archive entry name as a relative path, `None` when it would leave the directory it is extracted to
+fn enclosed_path(name: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(name.replace('\\', "/"));
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        .then_some(path)
+}
+
+/// The built in extractors, followed by the external `7z` from the settings
+#[must_use]
+pub fn extractors(settings: &Settings) -> Vec<Box<dyn ArchiveExtractor>> {
+    vec![
+        Box::new(ZipExtractor),
+        Box::new(SevenZipExtractor),
+        Box::new(ExternalSevenZip::new(settings.seven_zip())),
+    ]
+}
+
+/// Extract an archive with the first extractor that supports its format, falling back to the next ones when it fails
+///
+/// # Errors
+/// Errors with the reason each extractor gave when none of them can extract the archive
+pub fn extract(extractors: &[Box<dyn ArchiveExtractor>], archive: &Path, dest: &Path) -> Result<(), ToryggError> {
+    let format = ArchiveFormat::detect(archive)?;
+
+    let mut reasons = Vec::new();
+    for extractor in extractors.iter().filter(|extractor| extractor.supports(format)) {
+        info!("Extracting {} ({format}) with {}", archive.display(), extractor.name());
+        match extractor.extract(archive, dest) {
+            Ok(()) => return Ok(()),
+            Err(reason) => {
+                warn!("{} could not extract {}: {reason}", extractor.name(), archive.display());
+                reasons.push(format!("{}: {reason}", extractor.name()));
+
+                // Start over from an empty directory
+                fs::remove_dir_all(dest)?;
+                fs::create_dir(dest)?;
+            },
+        }
+    }
+
+    if reasons.is_empty() {
+        reasons.push("no extractor supports it".to_owned());
+    }
+
+    Err(ToryggError::ExtractionFailed { archive: archive.to_path_buf(), format, reason: reasons.join("; ") })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use super::*;
+
+    #[test]
+    fn zip() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("mod.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        zip.add_directory("Data/", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.start_file("Data/mod.esp", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"plugin").unwrap();
+        zip.finish().unwrap();
+
+        assert_eq!(ArchiveFormat::detect(&archive).unwrap(), ArchiveFormat::Zip);
+        let dest = dir.path().join("out");
+        fs::create_dir(&dest).unwrap();
+        extract(&[Box::new(ZipExtractor)], &archive, &dest).unwrap();
+        assert_eq!(fs::read_to_string(dest.join("Data/mod.esp")).unwrap(), "plugin");
+
+        let text = dir.path().join("mod.rar");
+        fs::write(&text, "Rar!\x1a\x07\x01\x00").unwrap();
+        let failed = extract(&[Box::new(ZipExtractor), Box::new(ExternalSevenZip::new(dir.path().join("7z")))], &text, &dest);
+        assert!(matches!(failed, Err(ToryggError::ExtractionFailed { format: ArchiveFormat::Rar, reason, .. }) if reason.contains("not installed")));
+
+        assert_eq!(enclosed_path("Data\\a.esp"), Some(PathBuf::from("Data/a.esp")));
+        assert_eq!(enclosed_path("../a.esp"), None);
+        assert_eq!(enclosed_path("/a.esp"), None);
+    }
+}
@@ -0,0 +1,263 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::info;
+use walkdir::WalkDir;
+use crate::context::Context;
+use crate::error::ToryggError;
+use crate::games::SteamApp;
+use crate::inspect::is_bain_package;
+use crate::util::find_case_insensitive_path;
+
+/// Chooses which BAIN sub-packages to install, by index, given the packages in the order they are installed in
+pub(crate) type BainCallback = fn(&[BainPackage]) -> Vec<usize>;
+
+/// A numbered sub-package of a BAIN (Wrye Bash) installer, eg. `00 Core` or `20 Patches`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BainPackage {
+    name: String,
+    path: PathBuf,
+    number: u32,
+    selected: bool,
+}
+
+impl BainPackage {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the package is chosen by default, by the `wizard.txt` or otherwise the `00` packages
+    #[must_use]
+    pub fn selected(&self) -> bool {
+        self.selected
+    }
+}
+
+/// The sub-packages of a BAIN installer, in numeric order, empty when `mod_root` is not one
+///
+/// A BAIN installer has at least two numbered folders at its top.
+pub(crate) fn packages(mod_root: &Path) -> Result<Vec<BainPackage>, ToryggError> {
+    let mut packages = Vec::new();
+    let mut wizard = None;
+    for entry in fs::read_dir(mod_root)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if is_bain_package(&path) {
+            let number = name.chars().take_while(char::is_ascii_digit).collect::<String>().parse().unwrap_or(u32::MAX);
+            packages.push(BainPackage { name, path, number, selected: number == 0 });
+        } else if path.is_file() && name.eq_ignore_ascii_case("wizard.txt") {
+            wizard = Some(path);
+        }
+    }
+
+    if packages.len() < 2 {
+        return Ok(Vec::new());
+    }
+
+    packages.sort_by_key(|package| (package.number, package.name.to_lowercase()));
+
+    if let Some(wizard) = wizard {
+        let script = String::from_utf8_lossy(&fs::read(&wizard)?).to_string();
+        if let Some(selected) = wizard_selection(&script, &packages) {
+            info!("using the sub-packages selected by {}", wizard.display());
+            for (package, selected) in packages.iter_mut().zip(selected) {
+                package.selected = selected;
+            }
+        }
+    }
+
+    if !packages.iter().any(|package| package.selected) {
+        packages[0].selected = true;
+    }
+
+    Ok(packages)
+}
+
+/// The strings in a wizard statement, which are in single or double quotes
+fn quoted(line: &str) -> Vec<&str> {
+    let mut strings = Vec::new();
+    let mut rest = line;
+    while let Some(start) = rest.find(['"', '\'']) {
+        let quote = rest[start..].chars().next().unwrap_or('"');
+        let Some(end) = rest[start + 1..].find(quote) else {
+            break;
+        };
+
+        strings.push(&rest[start + 1..start + 1 + end]);
+        rest = &rest[start + end + 2..];
+    }
+
+    strings
+}
+
+/// A `SelectOne` or `SelectMany` dialog being run
+struct Select {
+    defaults: Vec<String>,
+    running: bool,
+    matched: bool,
+    parent_running: bool,
+}
+
+/// The sub-packages a `wizard.txt` selects when every dialog is left at its default options
+///
+/// Only selecting sub-packages is run, conditions cannot be evaluated so `If` blocks are skipped.
+/// `None` when the wizard selects nothing this way.
+fn wizard_selection(script: &str, packages: &[BainPackage]) -> Option<Vec<bool>> {
+    let mut selected = vec![false; packages.len()];
+    let mut selects: Vec<Select> = Vec::new();
+    let mut ifs = 0_usize;
+    let mut running = true;
+
+    let script = script.replace("\\\r\n", " ").replace("\\\n", " ");
+    for line in script.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with(';')) {
+        let keyword = line.split_whitespace().next().unwrap_or_default();
+        match keyword {
+            "If" => ifs += 1,
+            "EndIf" => ifs = ifs.saturating_sub(1),
+            _ if ifs > 0 => {},
+            "SelectOne" | "SelectMany" => {
+                // The prompt is followed by option, description, image triples, defaults start with `|`
+                let options = quoted(line).into_iter().skip(1).step_by(3).collect::<Vec<_>>();
+                let mut defaults = options.iter()
+                    .filter_map(|option| option.strip_prefix('|'))
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>();
+
+                // `SelectOne` always chooses an option, the first when none is marked as the default
+                if keyword == "SelectOne" && defaults.is_empty() {
+                    defaults.extend(options.first().map(|option| (*option).to_owned()));
+                }
+                selects.push(Select { defaults, running: false, matched: false, parent_running: running });
+                running = false;
+            },
+            "Case" => if let Some(select) = selects.last_mut() {
+                let option = quoted(line).first().copied().unwrap_or_default();
+                select.running = select.parent_running && select.defaults.iter().any(|default| default == option);
+                select.matched |= select.running;
+                running = select.running;
+            },
+            "Default" => if let Some(select) = selects.last_mut() {
+                select.running = select.parent_running && !select.matched;
+                running = select.running;
+            },
+            "Break" => if let Some(select) = selects.last_mut() {
+                select.running = false;
+                running = false;
+            },
+            "EndSelect" => if let Some(select) = selects.pop() {
+                running = select.parent_running;
+            },
+            _ if !running => {},
+            "SelectAll" => selected.fill(true),
+            "DeSelectAll" => selected.fill(false),
+            "SelectSubPackage" | "DeSelectSubPackage" => {
+                let name = quoted(line).first().copied().unwrap_or_default();
+                if let Some(index) = packages.iter().position(|package| package.name.eq_ignore_ascii_case(name)) {
+                    selected[index] = keyword == "SelectSubPackage";
+                }
+            },
+            "Return" => break,
+            "Cancel" => return None,
+            _ => {},
+        }
+    }
+
+    selected.contains(&true).then_some(selected)
+}
+
+/// Install the chosen sub-packages of a BAIN installer, later packages overwriting files of earlier ones
+pub(crate) fn bain_install(context: &Context, game: &SteamApp, packages: &[BainPackage], name: &String, bain_callback: BainCallback) -> Result<(), ToryggError> {
+    let mut chosen = bain_callback(packages);
+    chosen.sort_unstable();
+    chosen.dedup();
+    let chosen = chosen.into_iter().filter_map(|index| packages.get(index)).collect::<Vec<_>>();
+    if chosen.is_empty() {
+        return Err(ToryggError::Other("no BAIN sub-packages were chosen".to_owned()));
+    }
+
+    let install_path = context.mods_dir(game)?.maybe_create_child_directory(name)?;
+    merge(&chosen, install_path.as_ref())
+}
+
+/// Copy the packages into `install_path` in order, matching folders regardless of case
+fn merge(packages: &[&BainPackage], install_path: &Path) -> Result<(), ToryggError> {
+    for package in packages {
+        info!("installing sub-package {}", package.name);
+        for entry in WalkDir::new(&package.path).min_depth(1).into_iter().filter_map(Result::ok) {
+            let relative_path = entry.path().strip_prefix(&package.path).unwrap_or(entry.path());
+            let to = install_path.join(find_case_insensitive_path(install_path, relative_path));
+
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(to)?;
+            } else {
+                fs::copy(entry.path(), to)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bain_dir(files: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in files {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn packages_and_merge() {
+        let dir = bain_dir(&["10 Options/Textures/a.dds", "00 Core/textures/a.dds", "00 Core/Mod.esp", "100 Extra/b.esp", "readme.txt"]);
+        let packages = packages(dir.path()).unwrap();
+        assert_eq!(packages.iter().map(BainPackage::name).collect::<Vec<_>>(), ["00 Core", "10 Options", "100 Extra"]);
+        assert_eq!(packages.iter().map(BainPackage::selected).collect::<Vec<_>>(), [true, false, false]);
+
+        let install = tempfile::tempdir().unwrap();
+        merge(&[&packages[0], &packages[1]], install.path()).unwrap();
+        assert_eq!(fs::read_to_string(install.path().join("textures/a.dds")).unwrap(), "10 Options/Textures/a.dds");
+        assert!(install.path().join("Mod.esp").is_file());
+        assert!(!install.path().join("b.esp").exists());
+        assert!(!install.path().join("readme.txt").exists());
+
+        assert!(super::packages(&dir.path().join("00 Core")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn wizard() {
+        let dir = bain_dir(&["00 Core/a.esp", "10 1K/a.dds", "20 2K/a.dds", "30 Patch/b.esp"]);
+        fs::write(dir.path().join("wizard.txt"), concat!(
+            "; Texture size\n",
+            "SelectSubPackage \"00 Core\"\n",
+            "SelectOne \"Texture size\", \\\n",
+            "    \"1K\", \"Smaller\", \"\", \\\n",
+            "    \"|2K\", \"Larger\", \"\"\n",
+            "    Case \"1K\"\n",
+            "        SelectSubPackage \"10 1K\"\n",
+            "        Break\n",
+            "    Case \"2K\"\n",
+            "        SelectSubPackage '20 2K'\n",
+            "        Break\n",
+            "EndSelect\n",
+            "If DataFileExists(\"Other.esp\")\n",
+            "    SelectSubPackage \"30 Patch\"\n",
+            "EndIf\n",
+        )).unwrap();
+
+        let packages = packages(dir.path()).unwrap();
+        assert_eq!(packages.iter().map(BainPackage::selected).collect::<Vec<_>>(), [true, false, true, false]);
+
+        // Without a default the first option is chosen
+        let wizard = fs::read_to_string(dir.path().join("wizard.txt")).unwrap().replace("\"|2K\"", "\"2K\"");
+        fs::write(dir.path().join("wizard.txt"), wizard).unwrap();
+        let packages = super::packages(dir.path()).unwrap();
+        assert_eq!(packages.iter().map(BainPackage::selected).collect::<Vec<_>>(), [true, true, false, false]);
+    }
+}
@@ -0,0 +1,227 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use torygg_vdf::{Options, Vdf};
+use crate::error::ToryggError;
+use crate::steam::SteamLibrary;
+
+/// A steam compatibility tool, such as an official or custom Proton build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatTool {
+    name: String,
+    display_name: String,
+    install_dir: PathBuf,
+}
+
+impl CompatTool {
+    /// The name steam refers to the tool by in `config.vdf`, eg. `proton_experimental` or `GE-Proton9-20`
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    #[must_use]
+    pub fn install_dir(&self) -> &Path {
+        &self.install_dir
+    }
+
+    /// The `proton` script used to run windows programs with the tool
+    #[must_use]
+    pub fn proton(&self) -> PathBuf {
+        self.install_dir.join("proton")
+    }
+
+    /// Every tool installed for a steam installation, custom tools first
+    ///
+    /// # Errors
+    /// Errors when the libraries cannot be read or a tool's `compatibilitytool.vdf` is malformed
+    pub fn all(steam_root: &Path) -> Result<Vec<CompatTool>, ToryggError> {
+        let mut tools = Vec::new();
+        for dir in custom_tool_dirs(steam_root) {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue
+            };
+
+            for entry in entries {
+                let path = entry?.path().join("compatibilitytool.vdf");
+                if path.is_file() {
+                    tools.extend(read_compatibilitytool_vdf(&path)?);
+                }
+            }
+        }
+
+        for library in SteamLibrary::all(steam_root)? {
+            let Ok(entries) = fs::read_dir(library.steamapps_dir().join("common")) else {
+                continue
+            };
+
+            for entry in entries {
+                let install_dir = entry?.path();
+                let display_name = install_dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+                if let Some(name) = official_name(&display_name).filter(|_| install_dir.join("proton").is_file()) {
+                    tools.push(CompatTool { name, display_name, install_dir });
+                }
+            }
+        }
+
+        Ok(tools)
+    }
+
+    /// Find an installed tool by the name steam refers to it by
+    ///
+    /// # Errors
+    /// Errors when the tools cannot be read or none has the name
+    pub fn find(steam_root: &Path, name: &str) -> Result<CompatTool, ToryggError> {
+        Self::all(steam_root)?.into_iter()
+            .find(|tool| tool.name == name)
+            .ok_or_else(|| ToryggError::CompatToolNotFound(name.to_owned()))
+    }
+
+    /// The name of the tool configured for an app in steam's `config.vdf`
+    ///
+    /// An override set for the app takes precedence over the default tool, set for app 0.
+    ///
+    /// # Errors
+    /// Errors when `config.vdf` cannot be read
+    pub fn configured_name(steam_root: &Path, appid: usize) -> Result<Option<String>, ToryggError> {
+        let root = torygg_vdf::parse_file(steam_root.join("config/config.vdf"), &Options::default())?;
+        let Some(Vdf::Object(mapping)) = root.get_path("InstallConfigStore/Software/Valve/Steam/CompatToolMapping") else {
+            return Ok(None)
+        };
+
+        // An empty name means the app follows the default
+        let name = |appid: usize| {
+            mapping.get_path(format!("{appid}/name"))
+                .and_then(Vdf::as_str)
+                .filter(|name| !name.is_empty())
+                .map(str::to_owned)
+        };
+
+        Ok(name(appid).or_else(|| name(0)))
+    }
+
+    /// The tool steam runs an app with
+    ///
+    /// # Errors
+    /// Errors when no tool is configured for the app or the configured tool is not installed
+    pub fn for_app(steam_root: &Path, appid: usize) -> Result<CompatTool, ToryggError> {
+        let name = Self::configured_name(steam_root, appid)?.ok_or(ToryggError::CompatToolNotConfigured(appid))?;
+        Self::find(steam_root, &name)
+    }
+}
+
+/// Directories custom tools are installed to, in the order steam checks them
+fn custom_tool_dirs(steam_root: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![steam_root.join("compatibilitytools.d")];
+    if let Some(extra) = std::env::var_os("STEAM_EXTRA_COMPAT_TOOLS_PATHS") {
+        dirs.extend(std::env::split_paths(&extra));
+    }
+    dirs.push(PathBuf::from("/usr/local/share/steam/compatibilitytools.d"));
+    dirs.push(PathBuf::from("/usr/share/steam/compatibilitytools.d"));
+    dirs
+}
+
+fn read_compatibilitytool_vdf(path: &Path) -> Result<Vec<CompatTool>, ToryggError> {
+    let root = torygg_vdf::parse_file(path, &Options::default())?;
+    let Some(Vdf::Object(tools)) = root.get_path("compatibilitytools/compat_tools") else {
+        return Ok(Vec::new())
+    };
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    Ok(tools.iter()
+        .map(|(name, tool)| CompatTool {
+            name: name.to_owned(),
+            display_name: tool.get("display_name").and_then(Vdf::as_str).unwrap_or(name).to_owned(),
+            // Collecting the components drops the `.` that install paths usually are
+            install_dir: dir.join(tool.get("install_path").and_then(Vdf::as_str).unwrap_or(".")).components().collect(),
+        })
+        .collect())
+}
+
+/// The name steam uses for an official Proton from its directory name
+///
+/// `Proton 9.0` is `proton_9`, `Proton 5.13` is `proton_513`, `Proton 3.7 Beta` is `proton_37_beta` and
+/// `Proton - Experimental` is `proton_experimental`. A note in brackets, as in `Proton 9.0 (Beta)`, is not part of it.
+fn official_name(dir_name: &str) -> Option<String> {
+    let rest = dir_name.strip_prefix("Proton")?.trim_start_matches([' ', '-']).trim();
+    if rest.is_empty() {
+        return None;
+    }
+
+    let name = if rest.starts_with(|char: char| char.is_ascii_digit()) {
+        let end = rest.find([' ', '(']).unwrap_or(rest.len());
+        let (version, suffix) = (&rest[..end], rest[end..].trim());
+        let version = version.strip_suffix(".0").unwrap_or(version).replace('.', "");
+        if suffix.is_empty() || suffix.starts_with('(') {
+            version
+        } else {
+            format!("{version}_{}", suffix.to_lowercase().replace(' ', "_"))
+        }
+    } else {
+        rest.to_lowercase().replace(' ', "_")
+    };
+
+    Some(format!("proton_{name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn official_names() {
+        assert_eq!(official_name("Proton 9.0").as_deref(), Some("proton_9"));
+        assert_eq!(official_name("Proton 9.0 (Beta)").as_deref(), Some("proton_9"));
+        assert_eq!(official_name("Proton 5.13").as_deref(), Some("proton_513"));
+        assert_eq!(official_name("Proton 3.7 Beta").as_deref(), Some("proton_37_beta"));
+        assert_eq!(official_name("Proton - Experimental").as_deref(), Some("proton_experimental"));
+        assert_eq!(official_name("Proton Hotfix").as_deref(), Some("proton_hotfix"));
+        assert_eq!(official_name("Proton"), None);
+        assert_eq!(official_name("Skyrim Special Edition"), None);
+    }
+
+    #[test]
+    fn configured_names() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("config")).unwrap();
+        fs::write(dir.path().join("config/config.vdf"), r#""InstallConfigStore"
+{
+	"Software"
+	{
+		"Valve"
+		{
+			"Steam"
+			{
+				"CompatToolMapping"
+				{
+					"0"
+					{
+						"name"		"proton_9"
+					}
+					"489830"
+					{
+						"name"		"GE-Proton9-20"
+					}
+					"377160"
+					{
+						"name"		""
+					}
+				}
+			}
+		}
+	}
+}
+"#).unwrap();
+
+        assert_eq!(CompatTool::configured_name(dir.path(), 489_830).unwrap().as_deref(), Some("GE-Proton9-20"));
+        assert_eq!(CompatTool::configured_name(dir.path(), 377_160).unwrap().as_deref(), Some("proton_9"));
+        assert_eq!(CompatTool::configured_name(dir.path(), 22_380).unwrap().as_deref(), Some("proton_9"));
+
+        fs::write(dir.path().join("config/config.vdf"), "InstallConfigStore { Software { } }").unwrap();
+        assert_eq!(CompatTool::configured_name(dir.path(), 489_830).unwrap(), None);
+    }
+}
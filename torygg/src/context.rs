@@ -0,0 +1,259 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use log::warn;
+use crate::error::ToryggError;
+use crate::existing_directory::ExistingDirectory;
+use crate::games::{SteamApp, SKYRIM_SPECIAL_EDITION};
+use crate::settings::Settings;
+use crate::steam;
+
+/// File next to the executable that makes it keep everything in a portable instance beside it
+pub const PORTABLE_MARKER: &str = "torygg.portable";
+
+/// Where torygg keeps its files, along with the settings read from them
+///
+/// Everything torygg reads or writes goes through a context, so several can be used in one process.
+#[derive(Debug, Clone)]
+pub struct Context {
+    config_dir: ExistingDirectory,
+    data_dir: ExistingDirectory,
+    steam_root: Option<PathBuf>,
+    settings: Settings,
+}
+
+impl Context {
+    /// Use the given config and data directories, creating them if they do not exist
+    ///
+    /// # Errors
+    /// Errors when the directories cannot be created or `config.toml` is invalid
+    pub fn new(config_dir: impl Into<PathBuf>, data_dir: impl Into<PathBuf>) -> Result<Context, ToryggError> {
+        let config_dir = config_dir.into();
+        let data_dir = data_dir.into();
+        fs::create_dir_all(&config_dir)?;
+        fs::create_dir_all(&data_dir)?;
+
+        let config_dir = ExistingDirectory::try_from(config_dir)?;
+        let data_dir = ExistingDirectory::try_from(data_dir)?;
+        let settings = Settings::read(&config_dir.as_ref().join("config.toml"))?;
+        let context = Context { config_dir, data_dir, steam_root: None, settings };
+
+        if let Err(e) = context.migrate_legacy_layout() {
+            warn!("failed to move mods and profiles into the Skyrim Special Edition directories: {e}");
+        }
+
+        Ok(context)
+    }
+
+    /// A self-contained instance, keeping its settings and profiles in `dir/config` and its mods, backups
+    /// and state in `dir/data`
+    ///
+    /// # Errors
+    /// Errors when the directories cannot be created or `config.toml` is invalid
+    pub fn portable(dir: impl Into<PathBuf>) -> Result<Context, ToryggError> {
+        let dir = dir.into();
+        Context::new(dir.join("config"), dir.join("data"))
+    }
+
+    /// A named instance in the [`Context::instances_dir`], created if it does not exist
+    ///
+    /// # Errors
+    /// Errors when the name is not a valid directory name, the directories cannot be created or
+    /// `config.toml` is invalid
+    pub fn instance(name: &str) -> Result<Context, ToryggError> {
+        let mut components = Path::new(name).components();
+        if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+            return Err(ToryggError::InvalidInstanceName(name.to_owned()));
+        }
+
+        Context::portable(Self::instances_dir()?.join(name))
+    }
+
+    /// The directory named instances are kept in, eg. `~/.local/share/torygg-instances`
+    ///
+    /// It is beside rather than in the default data directory, so instances are never mistaken for its contents.
+    ///
+    /// # Errors
+    /// Errors when the users data directory cannot be determined
+    pub fn instances_dir() -> Result<PathBuf, ToryggError> {
+        let data = dirs::data_dir().ok_or_else(|| ToryggError::Other("could not find location for data directory".to_owned()))?;
+        Ok(data.join("torygg-instances"))
+    }
+
+    /// The names of every named instance
+    ///
+    /// # Errors
+    /// Errors when the instances directory cannot be read
+    pub fn instances() -> Result<Vec<String>, ToryggError> {
+        let dir = Self::instances_dir()?;
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut instances = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.path().is_dir() {
+                instances.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        instances.sort();
+        Ok(instances)
+    }
+
+    /// The directory of the running executable when it has a [`PORTABLE_MARKER`] next to it
+    #[must_use]
+    pub fn portable_dir() -> Option<PathBuf> {
+        let exe = std::env::current_exe().ok()?;
+        let dir = exe.parent()?;
+        dir.join(PORTABLE_MARKER).is_file().then(|| dir.to_path_buf())
+    }
+
+    /// Use the given steam installation rather than the `steam_root` setting or searching for one
+    ///
+    /// `TORYGG_STEAM_ROOT` still takes precedence.
+    #[must_use]
+    pub fn with_steam_root(mut self, path: impl Into<PathBuf>) -> Context {
+        self.steam_root = Some(path.into());
+        self
+    }
+
+    /// Move mods and profiles from before torygg managed several games into the directories for Skyrim Special Edition
+    fn migrate_legacy_layout(&self) -> std::io::Result<()> {
+        let game = &SKYRIM_SPECIAL_EDITION;
+
+        let data = self.data_dir();
+        if data.join("Mods").is_dir() && !data.join(game.id()).exists() {
+            fs::create_dir(data.join(game.id()))?;
+            for dir in ["Mods", "Backup"] {
+                if data.join(dir).exists() {
+                    fs::rename(data.join(dir), data.join(game.id()).join(dir))?;
+                }
+            }
+        }
+
+        let config = self.config_dir();
+        if !config.join(game.id()).exists() {
+            let profiles = fs::read_dir(config)?
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.join("profile.toml").is_file())
+                .collect::<Vec<_>>();
+
+            if !profiles.is_empty() {
+                fs::create_dir(config.join(game.id()))?;
+                for profile in profiles {
+                    fs::rename(&profile, config.join(game.id()).join(profile.file_name().unwrap_or_default()))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The directory profiles, tool configurations and `config.toml` are kept in
+    #[must_use]
+    pub fn config_dir(&self) -> &Path {
+        self.config_dir.as_ref()
+    }
+
+    /// The directory mods, backups and the state are kept in
+    #[must_use]
+    pub fn data_dir(&self) -> &Path {
+        self.data_dir.as_ref()
+    }
+
+    #[must_use]
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Replace the settings, writing them to `config.toml`
+    ///
+    /// # Errors
+    /// Errors when the file cannot be written
+    pub fn set_settings(&mut self, settings: Settings) -> Result<(), ToryggError> {
+        settings.write(&self.config_dir().join("config.toml"))?;
+        self.settings = settings;
+        Ok(())
+    }
+
+    /// The steam installation directory
+    ///
+    /// `TORYGG_STEAM_ROOT`, [`Context::with_steam_root`] or the `steam_root` setting, in that order, take
+    /// precedence over searching the [`steam::steam_root_candidates`].
+    ///
+    /// # Errors
+    /// Errors with every location tried when none of them is a steam installation, or when there are none to
+    /// try as the home directory is unknown
+    pub fn steam_root(&self) -> Result<PathBuf, ToryggError> {
+        let chosen = std::env::var_os("TORYGG_STEAM_ROOT").map(PathBuf::from)
+            .or_else(|| self.steam_root.clone())
+            .or_else(|| self.settings.steam_root());
+
+        steam::steam_root(chosen)
+    }
+
+    /// The directory in which a games profiles and tools are kept
+    pub(crate) fn game_config_dir(&self, game: &SteamApp) -> Result<ExistingDirectory, ToryggError> {
+        self.config_dir.maybe_create_child_directory(game.id())
+    }
+
+    /// The directory in which a games mods and backups are kept
+    pub(crate) fn game_data_dir(&self, game: &SteamApp) -> Result<ExistingDirectory, ToryggError> {
+        self.data_dir.maybe_create_child_directory(game.id())
+    }
+
+    /// The directory in which a games mods are kept
+    pub(crate) fn mods_dir(&self, game: &SteamApp) -> Result<ExistingDirectory, ToryggError> {
+        self.game_data_dir(game)?.maybe_create_child_directory("Mods")
+    }
+}
+
+/// A context using the users config and data directories, eg. `~/.config/torygg` and `~/.local/share/torygg`
+///
+/// When the executable has a [`PORTABLE_MARKER`] next to it, the portable instance in its directory is
+/// used instead.
+///
+/// # Errors
+/// Errors when the directories cannot be determined or created, or `config.toml` is invalid
+pub fn init_default() -> Result<Context, ToryggError> {
+    if let Some(dir) = Context::portable_dir() {
+        return Context::portable(dir);
+    }
+
+    let config = dirs::config_dir().ok_or_else(|| ToryggError::Other("could not find location for config directory".to_owned()))?;
+    let data = dirs::data_dir().ok_or_else(|| ToryggError::Other("could not find location for data directory".to_owned()))?;
+    Context::new(config.join("torygg"), data.join("torygg"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portable() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = Context::portable(dir.path().join("main")).unwrap();
+        assert_eq!(context.config_dir(), dir.path().join("main/config"));
+        assert_eq!(context.data_dir(), dir.path().join("main/data"));
+        assert!(context.data_dir().is_dir());
+
+        for name in ["", ".", "..", "a/b", "/abs"] {
+            assert!(matches!(Context::instance(name), Err(ToryggError::InvalidInstanceName(_))), "{name}");
+        }
+
+        let default_data = dirs::data_dir().unwrap().join("torygg");
+        assert!(!Context::instances_dir().unwrap().starts_with(default_data));
+    }
+
+    #[test]
+    fn invalid_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config/config.toml");
+        fs::create_dir_all(config.parent().unwrap()).unwrap();
+        fs::write(&config, "deploy_method = \"hardlink\"\nunknown = [").unwrap();
+
+        assert!(matches!(Context::portable(dir.path()), Err(ToryggError::InvalidConfig { path, .. }) if path == config));
+        assert_eq!(fs::read_to_string(&config).unwrap(), "deploy_method = \"hardlink\"\nunknown = [");
+    }
+}
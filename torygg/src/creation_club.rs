@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::Path;
+use crate::error::ToryggError;
+
+/// The Creation Club content that comes with every copy of Skyrim Special Edition since 1.6
+const SKYRIM_FREE_CONTENT: [&str; 4] = [
+    "ccbgssse001-fish",
+    "ccqdrsse001-survivalmode",
+    "ccbgssse025-advdsgs",
+    "ccbgssse037-curios",
+];
+
+/// The Creation Club content installed for a game
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CreationClub {
+    listed: Vec<String>,
+    plugins: Vec<String>,
+    archives: Vec<String>,
+}
+
+impl CreationClub {
+    /// Read the `.ccc` file in the install directory and look for `cc*` plugins and archives in the data directory
+    pub(crate) fn read(install_dir: &Path, data_dir: &Path, ccc_file: Option<&str>) -> Result<CreationClub, ToryggError> {
+        let mut creation_club = CreationClub::default();
+
+        if let Some(path) = ccc_file.map(|file| install_dir.join(file)).filter(|path| path.is_file()) {
+            creation_club.listed = fs::read_to_string(path)?.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_owned)
+                .collect();
+        }
+
+        if data_dir.is_dir() {
+            for entry in fs::read_dir(data_dir)? {
+                let name = entry?.file_name().to_string_lossy().to_string();
+                let lower = name.to_lowercase();
+                if !lower.starts_with("cc") {
+                    continue;
+                }
+
+                if [".esl", ".esm", ".esp"].iter().any(|extension| lower.ends_with(extension)) {
+                    creation_club.plugins.push(name);
+                } else if [".bsa", ".ba2"].iter().any(|extension| lower.ends_with(extension)) {
+                    creation_club.archives.push(name);
+                }
+            }
+        }
+
+        creation_club.plugins.sort_by_key(|name| name.to_lowercase());
+        creation_club.archives.sort_by_key(|name| name.to_lowercase());
+        Ok(creation_club)
+    }
+
+    /// The plugins listed in the games `.ccc` file, in load order
+    #[must_use]
+    pub fn listed(&self) -> &[String] {
+        &self.listed
+    }
+
+    /// The `cc*` plugins in the data directory
+    #[must_use]
+    pub fn plugins(&self) -> &[String] {
+        &self.plugins
+    }
+
+    /// The `cc*` archives in the data directory
+    #[must_use]
+    pub fn archives(&self) -> &[String] {
+        &self.archives
+    }
+
+    /// Whether the `.ccc` file lists plugins beyond the free content every copy of Skyrim Special Edition
+    /// has, which is how the Anniversary Edition upgrade shows
+    ///
+    /// The `.ccc` file comes with the games files, so `cc*` plugins bought one at a time or from mods are
+    /// not mistaken for the upgrade.
+    #[must_use]
+    pub(crate) fn has_paid_skyrim_content(&self) -> bool {
+        self.listed.iter().any(|plugin| {
+            let stem = plugin.rsplit_once('.').map_or(plugin.as_str(), |(stem, _)| stem).to_lowercase();
+            !SKYRIM_FREE_CONTENT.contains(&stem.as_str())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("Data");
+        fs::create_dir(&data_dir).unwrap();
+        fs::write(dir.path().join("Skyrim.ccc"), "ccBGSSSE001-Fish.esm\nccQDRSSE001-SurvivalMode.esl\n\n").unwrap();
+        for file in ["ccBGSSSE001-Fish.esm", "ccBGSSSE001-Fish.bsa", "ccEEJSSE001-Hstead.esm", "ccMod.esp", "Skyrim.esm"] {
+            fs::write(data_dir.join(file), "").unwrap();
+        }
+
+        // Creation Club content bought on its own and mods named like it are not the upgrade
+        let creation_club = CreationClub::read(dir.path(), &data_dir, Some("Skyrim.ccc")).unwrap();
+        assert_eq!(creation_club.listed(), ["ccBGSSSE001-Fish.esm", "ccQDRSSE001-SurvivalMode.esl"]);
+        assert_eq!(creation_club.plugins(), ["ccBGSSSE001-Fish.esm", "ccEEJSSE001-Hstead.esm", "ccMod.esp"]);
+        assert_eq!(creation_club.archives(), ["ccBGSSSE001-Fish.bsa"]);
+        assert!(!creation_club.has_paid_skyrim_content());
+
+        fs::write(dir.path().join("Skyrim.ccc"), "ccBGSSSE001-Fish.esm\nccEEJSSE001-Hstead.esm\n").unwrap();
+        assert!(CreationClub::read(dir.path(), &data_dir, Some("Skyrim.ccc")).unwrap().has_paid_skyrim_content());
+    }
+}
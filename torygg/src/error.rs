@@ -1,6 +1,7 @@
 use std::io;
 use std::path::PathBuf;
 use thiserror::Error;
+use crate::archive::ArchiveFormat;
 
 #[derive(Error, Debug)]
 pub enum ToryggError {
@@ -10,6 +11,12 @@ pub enum ToryggError {
     #[error("profile '{0}' already exists")]
     ProfileAlreadyExists(String),
 
+    #[error("profile '{0}' could not be found")]
+    ProfileNotFound(String),
+
+    #[error("unknown game '{0}'")]
+    GameNotFound(String),
+
     #[error("torygg is currently deployed")]
     IsDeployed,
 
@@ -22,21 +29,81 @@ pub enum ToryggError {
     #[error("child failed")]
     ChildFailed,
 
+    #[error("steam could not be found, tried: {}", .0.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", "))]
+    SteamNotFound(Vec<PathBuf>),
+
+    #[error("steam could not be found as the home directory is unknown, set TORYGG_STEAM_ROOT or the steam_root setting")]
+    HomeNotFound,
+
     #[error("steam library could not be found")]
     SteamLibraryNotFound,
 
+    #[error("app {0} is not installed in any steam library")]
+    AppNotInstalled(usize),
+
+    #[error("steam is updating the game")]
+    GameUpdating,
+
+    #[error("app {0} could not be found in appinfo.vdf")]
+    AppInfoNotFound(usize),
+
+    #[error("{launcher} game '{name}' could not be found")]
+    LauncherGameNotFound { launcher: &'static str, name: String },
+
+    #[error("no compatibility tool is configured for app {0}")]
+    CompatToolNotConfigured(usize),
+
+    #[error("compatibility tool '{0}' is not installed")]
+    CompatToolNotFound(String),
+
+    #[error("{} is invalid: {reason}", .path.display())]
+    InvalidConfig { path: PathBuf, reason: String },
+
+    #[error("unknown setting '{0}'")]
+    UnknownSetting(String),
+
+    #[error("invalid value for {key}: {reason}")]
+    InvalidSetting { key: String, reason: String },
+
+    #[error("'{0}' is not a valid instance name")]
+    InvalidInstanceName(String),
+
+    #[error("failed to extract {} ({format}): {reason}", .archive.display())]
+    ExtractionFailed { archive: PathBuf, format: ArchiveFormat, reason: String },
+
     #[error("wine prefix could not be found")]
     PrefixNotFound,
 
+    #[error("{} is not a proton prefix", .0.display())]
+    NotAProtonPrefix(PathBuf),
+
+    #[error("executable {} could not be found", .0.display())]
+    ExecutableNotFound(PathBuf),
+
+    #[error("plugin checks are not supported for {0}")]
+    ChecksNotSupported(&'static str),
+
+    #[error("invalid PE file: {0}")]
+    InvalidPe(String),
+
+    #[error("no executable named '{0}' is configured")]
+    UnknownExecutable(String),
+
+    #[error("executable '{0}' already exists")]
+    ExecutableAlreadyExists(String),
+
     #[error("the path is not a directory")]
     NotADirectory(PathBuf),
 
     #[error("the directory \"{0:?}\" could not found")]
     DirectoryNotFound(PathBuf),
 
-    #[error("IO Error")]
+    #[error(transparent)]
     IOError(#[from] io::Error),
 
+    #[error(transparent)]
+    VdfError(#[from] torygg_vdf::VdfError),
+
     #[error("{0}")]
     Other(String),
 
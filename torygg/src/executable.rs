@@ -0,0 +1,108 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::context::Context;
+use crate::error::ToryggError;
+use crate::games::SteamApp;
+
+/// A modding tool, such as `SSEEdit` or `BodySlide`, run in the games prefix while mods are deployed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Executable {
+    name: String,
+    /// The windows executable, relative paths are within the games install directory
+    path: PathBuf,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+    working_dir: Option<PathBuf>,
+    /// Mod that files the tool writes to the data directory are collected into
+    output_mod: Option<String>,
+}
+
+impl Executable {
+    #[must_use]
+    pub fn new(name: &str, path: impl Into<PathBuf>) -> Executable {
+        Executable {
+            name: name.to_owned(),
+            path: path.into(),
+            args: Vec::new(),
+            working_dir: None,
+            output_mod: None,
+        }
+    }
+
+    /// Arguments passed to the executable
+    #[must_use]
+    pub fn with_args(mut self, args: Vec<String>) -> Executable {
+        self.args = args;
+        self
+    }
+
+    /// The directory the executable is run in, by default the one containing it
+    #[must_use]
+    pub fn with_working_dir(mut self, dir: impl Into<PathBuf>) -> Executable {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// The mod that new and modified files in the data directory are collected into
+    #[must_use]
+    pub fn with_output_mod(mut self, name: &str) -> Executable {
+        self.output_mod = Some(name.to_owned());
+        self
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[must_use]
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    #[must_use]
+    pub fn working_dir(&self) -> Option<&Path> {
+        self.working_dir.as_deref()
+    }
+
+    #[must_use]
+    pub fn output_mod(&self) -> Option<&str> {
+        self.output_mod.as_deref()
+    }
+}
+
+/// How the executables are stored in a games config directory
+#[derive(Default, Serialize, Deserialize)]
+struct ExecutablesFile {
+    #[serde(default, rename = "executable")]
+    executables: Vec<Executable>,
+}
+
+fn path(context: &Context, game: &SteamApp) -> Result<PathBuf, ToryggError> {
+    Ok(context.game_config_dir(game)?.as_ref().join("executables.toml"))
+}
+
+/// The executables configured for a game
+pub(crate) fn read(context: &Context, game: &SteamApp) -> Result<Vec<Executable>, ToryggError> {
+    let path = path(context, game)?;
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+
+    let file = toml::from_str::<ExecutablesFile>(&fs::read_to_string(&path)?)
+        .map_err(|e| ToryggError::Other(format!("failed to parse {}: {e}", path.display())))?;
+    Ok(file.executables)
+}
+
+pub(crate) fn write(context: &Context, game: &SteamApp, executables: Vec<Executable>) -> Result<(), ToryggError> {
+    let file = ExecutablesFile { executables };
+    let text = toml::to_string(&file).map_err(|e| ToryggError::Other(e.to_string()))?;
+    fs::write(path(context, game)?, text)?;
+    Ok(())
+}
@@ -5,8 +5,10 @@ use std::str::FromStr;
 use anyhow::anyhow;
 use log::info;
 use walkdir::WalkDir;
+use crate::context::Context;
 use crate::error::ToryggError;
-use crate::{config, modmanager};
+use crate::modmanager;
+use crate::games::SteamApp;
 use crate::util::find_case_insensitive_path;
 
 pub(crate) type FomodCallback = fn(&InstallStep) -> Vec<&Plugin>;
@@ -214,10 +216,8 @@ fn get_install_steps(module_config: &Path) -> Result<Vec<InstallStep>, ToryggErr
                     _ => {}
                 }
             }
-            xml::reader::XmlEvent::Characters(chars) => {
-                if is_desc {
-                    plugin.as_mut().unwrap().set_description(chars);
-                }
+            xml::reader::XmlEvent::Characters(chars) if is_desc => {
+                plugin.as_mut().unwrap().set_description(chars);
             }
             _ => {}
         }
@@ -226,7 +226,7 @@ fn get_install_steps(module_config: &Path) -> Result<Vec<InstallStep>, ToryggErr
     Ok(install_steps)
 }
 
-pub(crate) fn fomod_install(mod_root: &Path, fomod_dir: &Path, name: &String, fomod_callback: FomodCallback) -> Result<(), ToryggError> {
+pub(crate) fn fomod_install(context: &Context, game: &SteamApp, mod_root: &Path, fomod_dir: &Path, name: &String, fomod_callback: FomodCallback) -> Result<(), ToryggError> {
     let entries = fs::read_dir(fomod_dir)?
         .filter_map(Result::ok)
         .collect::<Vec<_>>();
@@ -242,7 +242,7 @@ pub(crate) fn fomod_install(mod_root: &Path, fomod_dir: &Path, name: &String, fo
     let Some(module_config) = module_config else {
         println!("no ModuleConfig.xml, doing regular install");
         fs::remove_dir_all(fomod_dir)?;
-        return modmanager::install_all(mod_root, name);
+        return modmanager::install_all(context, game, mod_root, name);
     };
 
     let install_steps = get_install_steps(&module_config).unwrap();
@@ -252,7 +252,7 @@ pub(crate) fn fomod_install(mod_root: &Path, fomod_dir: &Path, name: &String, fo
 
     let plugins = install_steps.iter().flat_map(fomod_callback).collect::<Vec<_>>();
 
-    let install_path = config::mods_dir().maybe_create_child_directory(name)?;
+    let install_path = context.mods_dir(game)?.maybe_create_child_directory(name)?;
 
     for plugin in plugins {
         let Some(files) = plugin.files() else {
@@ -271,7 +271,7 @@ pub(crate) fn fomod_install(mod_root: &Path, fomod_dir: &Path, name: &String, fo
 
                     let to = install_path.as_ref().join(&relative_path);
 
-                    info!("{from:?} -> {to:?}");
+                    info!("{} -> {}", from.display(), to.display());
                     fs::copy(from, to)?;
                 },
                 FileOrFolder::Folder { source, destination} => {
@@ -286,7 +286,7 @@ pub(crate) fn fomod_install(mod_root: &Path, fomod_dir: &Path, name: &String, fo
                         let relative_path = find_case_insensitive_path(&install_path, &relative_path);
                         let to = install_path.maybe_create_child_directory(destination)?.as_ref().join(relative_path);
 
-                        info!("{from:?} -> {to:?}");
+                        info!("{} -> {}", from.display(), to.display());
 
                         if from.is_dir() {
                             if !to.exists() {
@@ -1,14 +1,52 @@
-use std::collections::HashMap;
-use crate::util;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use torygg_vdf::{binary::AppInfo, Object, Vdf};
+use crate::compat::CompatTool;
 use crate::error::ToryggError;
+use crate::steam::{self, AppManifest, SteamLibrary};
 
+/// How a game records which plugins are active and their load order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginListFormat {
+    /// `Plugins.txt` lists the active plugins, load order follows the plugins modification times
+    Timestamps,
+    /// `Plugins.txt` lists every plugin in load order, active plugins are prefixed with `*`
+    Asterisk,
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub struct SteamApp {
+    id: &'static str,
     appid: usize,
-    name: &'static str
+    name: &'static str,
+    /// Directory within the install directory that mods are deployed to
+    data: &'static str,
+    /// Directory name in `My Games` and `AppData/Local`
+    user_dir: &'static str,
+    plugin_list: PluginListFormat,
+    ini_files: &'static [&'static str],
+    /// The games executable, relative to the install directory
+    executable: &'static str,
+    /// The script extenders loader, relative to the install directory
+    script_extender: &'static str,
+    /// The file in the install directory listing the games Creation Club plugins
+    creation_club: Option<&'static str>,
 }
 
 impl SteamApp {
+    /// Look up a game in [`GAMES`] by its id, eg. `skyrimse`
+    #[must_use]
+    pub fn by_id(id: &str) -> Option<&'static SteamApp> {
+        GAMES.iter().copied().find(|game| game.id.eq_ignore_ascii_case(id))
+    }
+
+    /// Short name used for the games directories and on the command line
+    #[must_use]
+    pub fn id(&self) -> &'static str {
+        self.id
+    }
+
     #[must_use]
     pub fn appid(&self) -> usize {
         self.appid
@@ -19,83 +57,101 @@ impl SteamApp {
         self.name
     }
 
-    /// The games installation directory
-    ///
-    /// # Errors
-    /// Errors when the directory cannot be found
-    pub fn install_dir(&self) -> Result<PathBuf, ToryggError> {
-        let path = util::steam_library(self)?
-            .join("steamapps/common")
-            .join(self.name);
+    #[must_use]
+    pub fn plugin_list_format(&self) -> PluginListFormat {
+        self.plugin_list
+    }
 
-        if path.exists() {
-            Ok(path)
-        } else {
-            Err(ToryggError::DirectoryNotFound(path))
-        }
+    pub(crate) fn data_dir_name(&self) -> &'static str {
+        self.data
+    }
+
+    pub(crate) fn user_dir_name(&self) -> &'static str {
+        self.user_dir
+    }
+
+    /// The names of the games ini files, which are kept in [`crate::GameInstall::config_dir`]
+    #[must_use]
+    pub fn ini_files(&self) -> &'static [&'static str] {
+        self.ini_files
+    }
+
+    /// The executable that starts the game, eg. `SkyrimSE.exe`
+    #[must_use]
+    pub fn executable(&self) -> &'static str {
+        self.executable
     }
 
-    /// The wine prefix associated with the game
+    /// The executable that starts the game with its script extender, eg. `skse64_loader.exe`
+    #[must_use]
+    pub fn script_extender_loader(&self) -> &'static str {
+        self.script_extender
+    }
+
+    /// The file listing the games Creation Club plugins, eg. `Skyrim.ccc`, for games with Creation Club content
+    #[must_use]
+    pub fn creation_club_file(&self) -> Option<&'static str> {
+        self.creation_club
+    }
+
+    /// The games entry in `appinfo.vdf` of a steam installation
     ///
     /// # Errors
-    /// Errors when the directory cannot be found
-    pub fn wine_pfx(&self) -> Result<PathBuf, ToryggError> {
-        let path = util::steam_library(self)?
-            .join("steamapps/compatdata")
-            .join(self.appid.to_string())
-            .join("pfx");
+    /// Errors when `appinfo.vdf` cannot be read or does not contain the game
+    pub fn app_info(&self, steam_root: &Path) -> Result<Object, ToryggError> {
+        let file = BufReader::new(File::open(steam::appinfo_vdf(steam_root))?);
+        let appid = u32::try_from(self.appid).map_err(|_| ToryggError::AppInfoNotFound(self.appid))?;
+        let app = AppInfo::new(file)?.find(appid)?
+            .ok_or(ToryggError::AppInfoNotFound(self.appid))?;
 
-        if path.exists() {
-            Ok(path)
-        } else {
-            Err(ToryggError::PrefixNotFound)
+        match app.data.get("appinfo") {
+            Some(Vdf::Object(appinfo)) => Ok(appinfo.clone()),
+            _ => Err(ToryggError::AppInfoNotFound(self.appid)),
         }
     }
 
-    /// The windows user directory in the wine prefix
+    /// The windows executables steam can launch for the game, relative to the install directory
     ///
     /// # Errors
-    /// Errors when the directory cannot be found
-    pub fn wine_user_dir(&self) -> Result<PathBuf, ToryggError> {
-        // Prioritise a path specified via environment variable
-        if let Some(str) = std::env::var_os("TORYGG_USER_DIRECTORY") {
-            let path = PathBuf::from(str);
-            return if path.exists() {
-                Ok(path)
-            } else {
-                Err(ToryggError::DirectoryNotFound(path))
-            }
-        }
+    /// Errors when the game cannot be found in `appinfo.vdf`
+    pub fn executables(&self, steam_root: &Path) -> Result<Vec<PathBuf>, ToryggError> {
+        let appinfo = self.app_info(steam_root)?;
+        let Some(Vdf::Object(launch)) = appinfo.get_path("config/launch") else {
+            return Ok(Vec::new())
+        };
 
-        let mut path = self.wine_pfx()?;
-        path.push("drive_c/users");
-
-        // When run through proton username is steamuser
-        let steamuser = path.join("steamuser");
-        if steamuser.exists() {
-            return Ok(steamuser)
-        }
+        let mut executables = Vec::new();
+        for (_, option) in launch {
+            let oslist = option.get_path("config/oslist").and_then(Vdf::as_str);
+            if oslist.is_some_and(|oslist| !oslist.contains("windows")) {
+                continue;
+            }
 
-        if let Some(current_user) =
-            std::env::vars().collect::<HashMap<_, _>>().get("USER")
-        {
-            let user_dir = path.join(current_user);
-            return if user_dir.exists() {
-                Ok(user_dir)
-            } else {
-                Err(ToryggError::DirectoryNotFound(user_dir))
+            if let Some(executable) = option.get("executable").and_then(Vdf::as_str) {
+                let executable = PathBuf::from(executable.replace('\\', "/"));
+                if !executables.contains(&executable) {
+                    executables.push(executable);
+                }
             }
         }
 
-        Err(ToryggError::Other("wine user dir not found".to_owned()))
+        Ok(executables)
     }
 
-    /// The games config directory
+    /// The library the game is installed in and its manifest
+    ///
+    /// # Errors
+    /// Errors when the game is not installed in any steam library
+    pub fn manifest(&self, steam_root: &Path) -> Result<(SteamLibrary, AppManifest), ToryggError> {
+        SteamLibrary::find_app(steam_root, self.appid)
+    }
+
+    /// The games installation directory
     ///
     /// # Errors
     /// Errors when the directory cannot be found
-    pub fn config_dir(&self) -> Result<PathBuf, ToryggError> {
-        let path = self.wine_user_dir()?.join(String::from("My Documents/My Games/") + self.name());
+    pub fn install_dir(&self, steam_root: &Path) -> Result<PathBuf, ToryggError> {
+        let path = self.manifest(steam_root)?.1.install_dir().to_path_buf();
         if path.exists() {
             Ok(path)
         } else {
@@ -103,28 +159,116 @@ impl SteamApp {
         }
     }
 
-    // Directory in which Plugins.txt is kept
-    /// The games appdata directory
+    /// The compatibility tool steam runs the game with
+    ///
+    /// # Errors
+    /// Errors when no tool is configured for the game or it is not installed
+    pub fn compat_tool(&self, steam_root: &Path) -> Result<CompatTool, ToryggError> {
+        CompatTool::for_app(steam_root, self.appid)
+    }
+
+    /// The proton prefix steam created for the game
     ///
     /// # Errors
     /// Errors when the directory cannot be found
-    pub fn appdata_dir(&self) -> Result<PathBuf, ToryggError> {
-        let path = self.wine_user_dir()?.join(String::from("Local Settings/Application Data/") + self.name());
+    pub fn wine_pfx(&self, steam_root: &Path) -> Result<PathBuf, ToryggError> {
+        let path = self.manifest(steam_root)?.0.steamapps_dir()
+            .join("compatdata")
+            .join(self.appid.to_string())
+            .join("pfx");
+
         if path.exists() {
             Ok(path)
         } else {
-            Err(ToryggError::DirectoryNotFound(path))
+            Err(ToryggError::PrefixNotFound)
         }
     }
 }
 
-// pub const SKYRIM: SteamApp = SteamApp {
-//     appid: 72850,
-//     name: "Skyrim"
-// };
-
-pub const SKYRIM_SPECIAL_EDITION: SteamApp = SteamApp {
+pub static SKYRIM_SPECIAL_EDITION: SteamApp = SteamApp {
+    id: "skyrimse",
     appid: 489830,
-    name: "Skyrim Special Edition"
+    name: "Skyrim Special Edition",
+    data: "Data",
+    user_dir: "Skyrim Special Edition",
+    plugin_list: PluginListFormat::Asterisk,
+    ini_files: &["Skyrim.ini", "SkyrimPrefs.ini", "SkyrimCustom.ini"],
+    executable: "SkyrimSE.exe",
+    script_extender: "skse64_loader.exe",
+    creation_club: Some("Skyrim.ccc"),
+};
+
+pub static SKYRIM_VR: SteamApp = SteamApp {
+    id: "skyrimvr",
+    appid: 611670,
+    name: "Skyrim VR",
+    data: "Data",
+    user_dir: "Skyrim VR",
+    plugin_list: PluginListFormat::Asterisk,
+    ini_files: &["SkyrimVR.ini", "SkyrimPrefs.ini"],
+    executable: "SkyrimVR.exe",
+    script_extender: "sksevr_loader.exe",
+    creation_club: None,
+};
+
+pub static ENDERAL_SPECIAL_EDITION: SteamApp = SteamApp {
+    id: "enderalse",
+    appid: 976620,
+    name: "Enderal Special Edition",
+    data: "Data",
+    user_dir: "Enderal Special Edition",
+    plugin_list: PluginListFormat::Asterisk,
+    ini_files: &["Enderal.ini", "EnderalPrefs.ini"],
+    executable: "SkyrimSE.exe",
+    script_extender: "skse64_loader.exe",
+    creation_club: None,
+};
+
+pub static FALLOUT_4: SteamApp = SteamApp {
+    id: "fallout4",
+    appid: 377160,
+    name: "Fallout 4",
+    data: "Data",
+    user_dir: "Fallout4",
+    plugin_list: PluginListFormat::Asterisk,
+    ini_files: &["Fallout4.ini", "Fallout4Prefs.ini", "Fallout4Custom.ini"],
+    executable: "Fallout4.exe",
+    script_extender: "f4se_loader.exe",
+    creation_club: Some("Fallout4.ccc"),
+};
+
+pub static FALLOUT_NEW_VEGAS: SteamApp = SteamApp {
+    id: "falloutnv",
+    appid: 22380,
+    name: "Fallout: New Vegas",
+    data: "Data",
+    user_dir: "FalloutNV",
+    plugin_list: PluginListFormat::Timestamps,
+    ini_files: &["Fallout.ini", "FalloutPrefs.ini"],
+    executable: "FalloutNV.exe",
+    script_extender: "nvse_loader.exe",
+    creation_club: None,
+};
+
+pub static OBLIVION: SteamApp = SteamApp {
+    id: "oblivion",
+    appid: 22330,
+    name: "Oblivion",
+    data: "Data",
+    user_dir: "Oblivion",
+    plugin_list: PluginListFormat::Timestamps,
+    ini_files: &["Oblivion.ini"],
+    executable: "Oblivion.exe",
+    script_extender: "obse_loader.exe",
+    creation_club: None,
 };
 
+/// Every game torygg can manage
+pub static GAMES: [&SteamApp; 6] = [
+    &SKYRIM_SPECIAL_EDITION,
+    &SKYRIM_VR,
+    &ENDERAL_SPECIAL_EDITION,
+    &FALLOUT_4,
+    &FALLOUT_NEW_VEGAS,
+    &OBLIVION,
+];
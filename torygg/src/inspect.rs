@@ -0,0 +1,232 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use crate::archive::ArchiveFormat;
+use crate::context::Context;
+use crate::error::ToryggError;
+use crate::modmanager;
+
+/// Folders the games load from their data directory
+const DATA_FOLDERS: [&str; 26] = [
+    "meshes", "textures", "sound", "music", "interface", "scripts", "seq", "strings", "video", "shadersfx",
+    "grass", "lodsettings", "materials", "terrain", "trees", "vis", "dialogueviews", "distantlod", "facegen",
+    "calientetools", "nemesis_engine", "netscriptframework", "skse", "f4se", "nvse", "obse",
+];
+
+/// Files the games load from their data directory: plugins and the archives they load
+const DATA_EXTENSIONS: [&str; 5] = ["esp", "esm", "esl", "bsa", "ba2"];
+
+const ARCHIVE_EXTENSIONS: [&str; 3] = ["zip", "7z", "rar"];
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().is_some_and(|extension| extensions.iter().any(|e| extension.eq_ignore_ascii_case(e)))
+}
+
+/// Whether an entry of a mods root is something the game loads from its data directory
+pub(crate) fn is_data_entry(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if path.is_dir() {
+        DATA_FOLDERS.iter().any(|folder| folder.eq_ignore_ascii_case(&name))
+    } else {
+        has_extension(path, &DATA_EXTENSIONS)
+    }
+}
+
+/// Whether a folder is a BAIN sub-package, which are named starting with a number, eg. `00 Core`
+pub(crate) fn is_bain_package(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.is_dir() && name.len() > 2 && name.chars().take(2).all(|char| char.is_ascii_digit())
+}
+
+/// Something in an archive that likely makes the installed mod not work
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveProblem {
+    /// An archive inside the archive, which is not extracted
+    NestedArchive(PathBuf),
+    /// A script extender loader, or other executable, which has to be next to the games executable rather
+    /// than in its data directory
+    ExecutableAtRoot(PathBuf),
+    /// Nothing the game loads, and no installer, was found
+    NoGameContent,
+}
+
+impl Display for ArchiveProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveProblem::NestedArchive(path) => write!(f, "{} is an archive within the archive", path.display()),
+            ArchiveProblem::ExecutableAtRoot(path) => write!(f, "{} is an executable, it belongs in a Root folder to be deployed next to the game", path.display()),
+            ArchiveProblem::NoGameContent => write!(f, "no plugins, archives or data folders were found"),
+        }
+    }
+}
+
+/// What an archive contains, as it would be installed
+#[derive(Debug, Clone)]
+pub struct ArchiveReport {
+    format: ArchiveFormat,
+    mod_roots: Vec<PathBuf>,
+    fomod: bool,
+    bain_packages: Vec<String>,
+    data_folders: Vec<String>,
+    plugins: Vec<String>,
+    archives: Vec<String>,
+    problems: Vec<ArchiveProblem>,
+}
+
+impl ArchiveReport {
+    #[must_use]
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    /// Where the mods files start, relative to the top of the archive, the first when there are several
+    ///
+    /// The rest of the report is about this folder.
+    #[must_use]
+    pub fn mod_root(&self) -> &Path {
+        &self.mod_roots[0]
+    }
+
+    /// Every folder that looks like the top of a mod, installing asks which one to use when there are several
+    #[must_use]
+    pub fn mod_roots(&self) -> &[PathBuf] {
+        &self.mod_roots
+    }
+
+    /// Whether the mod has a FOMOD installer
+    #[must_use]
+    pub fn fomod(&self) -> bool {
+        self.fomod
+    }
+
+    /// The BAIN sub-packages of the mod, empty when it is not a BAIN package
+    #[must_use]
+    pub fn bain_packages(&self) -> &[String] {
+        &self.bain_packages
+    }
+
+    /// Folders at the mod root the game loads from, eg. `meshes` or `SKSE`
+    #[must_use]
+    pub fn data_folders(&self) -> &[String] {
+        &self.data_folders
+    }
+
+    /// Plugins at the mod root
+    #[must_use]
+    pub fn plugins(&self) -> &[String] {
+        &self.plugins
+    }
+
+    /// Bethesda archives at the mod root
+    #[must_use]
+    pub fn archives(&self) -> &[String] {
+        &self.archives
+    }
+
+    #[must_use]
+    pub fn problems(&self) -> &[ArchiveProblem] {
+        &self.problems
+    }
+
+    /// Look through an extracted archive, `mod_roots` are where the mods files may start, at least one
+    fn read(format: ArchiveFormat, extract_dir: &Path, mod_roots: &[PathBuf]) -> Result<ArchiveReport, ToryggError> {
+        let mod_root = &mod_roots[0];
+        let mut report = ArchiveReport {
+            format,
+            mod_roots: mod_roots.iter().map(|root| root.strip_prefix(extract_dir).unwrap_or(root).to_path_buf()).collect(),
+            fomod: modmanager::find_fomod(mod_root)?.is_some(),
+            bain_packages: Vec::new(),
+            data_folders: Vec::new(),
+            plugins: Vec::new(),
+            archives: Vec::new(),
+            problems: Vec::new(),
+        };
+
+        let mut entries = fs::read_dir(mod_root)?.map(|entry| Ok(entry?.path())).collect::<Result<Vec<_>, ToryggError>>()?;
+        entries.sort();
+        for path in entries {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            if is_bain_package(&path) {
+                report.bain_packages.push(name);
+            } else if !is_data_entry(&path) {
+                if has_extension(&path, &["exe"]) {
+                    report.problems.push(ArchiveProblem::ExecutableAtRoot(report.mod_root().join(name)));
+                }
+            } else if path.is_dir() {
+                report.data_folders.push(name);
+            } else if has_extension(&path, &["bsa", "ba2"]) {
+                report.archives.push(name);
+            } else {
+                report.plugins.push(name);
+            }
+        }
+
+        // A couple of numbered folders are not enough to tell a BAIN package
+        if report.bain_packages.len() < 2 {
+            report.bain_packages.clear();
+        }
+
+        for entry in WalkDir::new(extract_dir).min_depth(1).into_iter().filter_map(Result::ok) {
+            if entry.file_type().is_file() && has_extension(entry.path(), &ARCHIVE_EXTENSIONS) {
+                let relative_path = entry.path().strip_prefix(extract_dir).unwrap_or(entry.path());
+                report.problems.push(ArchiveProblem::NestedArchive(relative_path.to_path_buf()));
+            }
+        }
+
+        let has_content = !report.data_folders.is_empty() || !report.plugins.is_empty() || !report.archives.is_empty();
+        if !has_content && !report.fomod && report.bain_packages.is_empty() {
+            report.problems.push(ArchiveProblem::NoGameContent);
+        }
+
+        Ok(report)
+    }
+}
+
+/// Extract an archive and report what it contains, without installing it
+///
+/// # Errors
+/// Errors when the archive cannot be extracted or read
+pub fn inspect_archive(context: &Context, archive: &Path) -> Result<ArchiveReport, ToryggError> {
+    let format = ArchiveFormat::detect(archive)?;
+    let extract_dir = modmanager::extract_archive(context, archive)?;
+    let mod_roots = modmanager::find_mod_roots(extract_dir.path(), archive)?;
+    ArchiveReport::read(format, extract_dir.path(), &mod_roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("My Mod");
+        for path in ["My Mod/Data/Meshes", "My Mod/Data/SKSE/Plugins", "Other/textures"] {
+            fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        for path in ["My Mod/Data/mod.esp", "My Mod/Data/mod.bsa", "My Mod/Data/skse64_loader.exe", "Other/textures/extra.7z"] {
+            fs::write(dir.path().join(path), "").unwrap();
+        }
+
+        let report = ArchiveReport::read(ArchiveFormat::Zip, dir.path(), &[root.join("Data")]).unwrap();
+        assert_eq!(report.mod_root(), Path::new("My Mod/Data"));
+        assert_eq!(report.data_folders(), ["Meshes", "SKSE"]);
+        assert_eq!(report.plugins(), ["mod.esp"]);
+        assert_eq!(report.archives(), ["mod.bsa"]);
+        assert!(!report.fomod() && report.bain_packages().is_empty());
+        assert_eq!(report.problems(), [
+            ArchiveProblem::ExecutableAtRoot(PathBuf::from("My Mod/Data/skse64_loader.exe")),
+            ArchiveProblem::NestedArchive(PathBuf::from("Other/textures/extra.7z")),
+        ]);
+
+        fs::create_dir_all(dir.path().join("Bain/00 Core")).unwrap();
+        fs::create_dir_all(dir.path().join("Bain/10 Option")).unwrap();
+        let report = ArchiveReport::read(ArchiveFormat::Zip, dir.path(), &[dir.path().join("Bain")]).unwrap();
+        assert_eq!(report.bain_packages(), ["00 Core", "10 Option"]);
+        assert!(!report.problems().contains(&ArchiveProblem::NoGameContent));
+
+        let report = ArchiveReport::read(ArchiveFormat::Zip, dir.path(), &[dir.path().join("Bain/00 Core")]).unwrap();
+        assert!(report.problems().contains(&ArchiveProblem::NoGameContent));
+    }
+}
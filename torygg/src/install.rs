@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::context::Context;
+use crate::error::ToryggError;
+use crate::creation_club::CreationClub;
+use crate::games::{SteamApp, SKYRIM_SPECIAL_EDITION};
+use crate::pe::{PeFile, Version};
+use crate::util::find_case_insensitive_path;
+
+/// Where to find a games installation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum InstallSource {
+    /// Found through steam's libraries and app manifests
+    #[default]
+    Steam,
+    /// A game directory and wine prefix given by the user
+    Manual {
+        install_dir: PathBuf,
+        prefix: Option<PathBuf>,
+    },
+    /// A GOG or Epic game installed through Heroic, by its app name
+    Heroic {
+        app_name: String,
+    },
+    /// A game installed through Lutris, by its slug
+    Lutris {
+        slug: String,
+    },
+}
+
+/// A located game installation, along with its wine prefix when it has one
+///
+/// Everything torygg needs from the game goes through here, so it works the same whatever
+/// the [`InstallSource`].
+#[derive(Debug, Clone)]
+pub struct GameInstall {
+    game: &'static SteamApp,
+    source: InstallSource,
+    install_dir: PathBuf,
+    prefix: Option<PathBuf>,
+    /// The steam installation, when there is one, whatever the source
+    steam_root: Option<PathBuf>,
+    /// The `user_directory` setting
+    user_directory: Option<PathBuf>,
+}
+
+impl GameInstall {
+    /// Find a game using the given source
+    ///
+    /// The `install_dir` and `prefix` settings for the game take precedence over what the source finds.
+    ///
+    /// # Errors
+    /// Errors when the source does not know of the game or its install directory does not exist
+    pub fn locate(context: &Context, game: &'static SteamApp, source: &InstallSource) -> Result<GameInstall, ToryggError> {
+        let settings = context.settings().game(game);
+        let (install_dir, prefix) = if let (Some(install_dir), Some(prefix)) = (settings.install_dir(), settings.prefix()) {
+            (install_dir.to_path_buf(), Some(prefix.to_path_buf()))
+        } else {
+            let (install_dir, prefix) = match source {
+                InstallSource::Steam => {
+                    let steam_root = context.steam_root()?;
+                    (game.install_dir(&steam_root)?, game.wine_pfx(&steam_root).ok())
+                },
+                InstallSource::Manual { install_dir, prefix } => (install_dir.clone(), prefix.clone()),
+                InstallSource::Heroic { app_name } => heroic::locate(app_name)?,
+                InstallSource::Lutris { slug } => lutris::locate(slug)?,
+            };
+
+            (settings.install_dir().map_or(install_dir, Path::to_path_buf), settings.prefix().map(Path::to_path_buf).or(prefix))
+        };
+
+        if !install_dir.is_dir() {
+            return Err(ToryggError::DirectoryNotFound(install_dir));
+        }
+
+        Ok(GameInstall {
+            game,
+            source: source.clone(),
+            install_dir,
+            prefix,
+            steam_root: context.steam_root().ok(),
+            user_directory: context.settings().user_directory(),
+        })
+    }
+
+    #[must_use]
+    pub fn game(&self) -> &'static SteamApp {
+        self.game
+    }
+
+    #[must_use]
+    pub fn source(&self) -> &InstallSource {
+        &self.source
+    }
+
+    #[must_use]
+    pub fn install_dir(&self) -> &Path {
+        &self.install_dir
+    }
+
+    /// The steam installation found when the game was located, even when it is not a steam game
+    #[must_use]
+    pub fn steam_root(&self) -> Option<&Path> {
+        self.steam_root.as_deref()
+    }
+
+    /// The wine prefix the game runs in, the directory containing `drive_c`
+    ///
+    /// # Errors
+    /// Errors when the game has no prefix
+    pub fn prefix(&self) -> Result<&Path, ToryggError> {
+        self.prefix.as_deref().filter(|prefix| prefix.exists()).ok_or(ToryggError::PrefixNotFound)
+    }
+
+    /// The directory proton keeps the prefix in, passed to it as `STEAM_COMPAT_DATA_PATH`
+    ///
+    /// # Errors
+    /// Errors when the game has no prefix or the prefix is not in a `pfx` directory
+    pub fn compat_data_dir(&self) -> Result<&Path, ToryggError> {
+        let prefix = self.prefix()?;
+        match prefix.parent() {
+            Some(parent) if prefix.ends_with("pfx") => Ok(parent),
+            _ => Err(ToryggError::NotAProtonPrefix(prefix.to_path_buf())),
+        }
+    }
+
+    /// Whether the launcher is currently changing the games files, only steam reports this
+    ///
+    /// # Errors
+    /// Errors when the steam app manifest cannot be read
+    pub fn is_updating(&self) -> Result<bool, ToryggError> {
+        match (&self.source, &self.steam_root) {
+            (InstallSource::Steam, Some(steam_root)) => Ok(self.game.manifest(steam_root)?.1.state_flags().is_updating()),
+            _ => Ok(false),
+        }
+    }
+
+    /// The directory mods are deployed to
+    #[must_use]
+    pub fn data_dir(&self) -> PathBuf {
+        self.install_dir.join(find_case_insensitive_path(&self.install_dir, self.game.data_dir_name()))
+    }
+
+    /// The games version, from the version resource of its executable
+    ///
+    /// # Errors
+    /// Errors when the executable cannot be read or has no version resource
+    pub fn game_version(&self) -> Result<Version, ToryggError> {
+        let path = self.install_dir.join(find_case_insensitive_path(&self.install_dir, self.game.executable()));
+        PeFile::read(&path)?.version().ok_or_else(|| ToryggError::InvalidPe(format!("{} has no version resource", path.display())))
+    }
+
+    /// The installed script extenders version, from the version resource of its loader
+    ///
+    /// # Errors
+    /// Errors when the loader exists but cannot be read
+    pub fn script_extender_version(&self) -> Result<Option<Version>, ToryggError> {
+        let path = self.install_dir.join(find_case_insensitive_path(&self.install_dir, self.game.script_extender_loader()));
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        Ok(PeFile::read(&path)?.version())
+    }
+
+    /// The installed Creation Club content
+    ///
+    /// # Errors
+    /// Errors when the `.ccc` file or the data directory cannot be read
+    pub fn creation_club(&self) -> Result<CreationClub, ToryggError> {
+        CreationClub::read(&self.install_dir, &self.data_dir(), self.game.creation_club_file())
+    }
+
+    /// Whether Skyrim Special Edition's Anniversary Edition upgrade is installed
+    ///
+    /// # Errors
+    /// Errors when the Creation Club content cannot be read
+    pub fn is_anniversary_edition(&self) -> Result<bool, ToryggError> {
+        Ok(self.game == &SKYRIM_SPECIAL_EDITION && self.creation_club()?.has_paid_skyrim_content())
+    }
+
+    /// The windows user directory in the wine prefix
+    ///
+    /// # Errors
+    /// Errors when the directory cannot be found
+    pub fn wine_user_dir(&self) -> Result<PathBuf, ToryggError> {
+        // Prioritise a path specified via environment variable or the settings
+        if let Some(path) = &self.user_directory {
+            return if path.exists() {
+                Ok(path.clone())
+            } else {
+                Err(ToryggError::DirectoryNotFound(path.clone()))
+            }
+        }
+
+        let path = self.prefix()?.join("drive_c/users");
+
+        // When run through proton username is steamuser
+        let steamuser = path.join("steamuser");
+        if steamuser.exists() {
+            return Ok(steamuser)
+        }
+
+        if let Some(current_user) = std::env::var_os("USER") {
+            let user_dir = path.join(current_user);
+            return if user_dir.exists() {
+                Ok(user_dir)
+            } else {
+                Err(ToryggError::DirectoryNotFound(user_dir))
+            }
+        }
+
+        Err(ToryggError::Other("wine user dir not found".to_owned()))
+    }
+
+    /// The games config directory, where its ini files are kept
+    ///
+    /// # Errors
+    /// Errors when the directory cannot be found
+    pub fn config_dir(&self) -> Result<PathBuf, ToryggError> {
+        let path = self.wine_user_dir()?.join("My Documents/My Games").join(self.game.user_dir_name());
+        if path.exists() {
+            Ok(path)
+        } else {
+            Err(ToryggError::DirectoryNotFound(path))
+        }
+    }
+
+    // Directory in which Plugins.txt is kept
+    /// The games appdata directory
+    ///
+    /// # Errors
+    /// Errors when the directory cannot be found
+    pub fn appdata_dir(&self) -> Result<PathBuf, ToryggError> {
+        let path = self.wine_user_dir()?.join("Local Settings/Application Data").join(self.game.user_dir_name());
+        if path.exists() {
+            Ok(path)
+        } else {
+            Err(ToryggError::DirectoryNotFound(path))
+        }
+    }
+
+    /// The games `Plugins.txt`
+    ///
+    /// # Errors
+    /// Errors when the appdata directory cannot be found
+    pub fn plugins_txt(&self) -> Result<PathBuf, ToryggError> {
+        let appdata_dir = self.appdata_dir()?;
+        Ok(appdata_dir.join(find_case_insensitive_path(&appdata_dir, "Plugins.txt")))
+    }
+}
+
+/// Directories a launcher may keep its config in, native first
+fn launcher_config_dirs(native: &str, flatpak: &str) -> Vec<PathBuf> {
+    let native = dirs::config_dir().map(|dir| dir.join(native));
+    let flatpak = dirs::home_dir().map(|home| home.join(".var/app").join(flatpak));
+    native.into_iter().chain(flatpak).collect()
+}
+
+fn not_found(launcher: &'static str, name: &str) -> ToryggError {
+    ToryggError::LauncherGameNotFound { launcher, name: name.to_owned() }
+}
+
+fn parse_error(path: &Path, e: impl std::fmt::Display) -> ToryggError {
+    ToryggError::Other(format!("failed to parse {}: {e}", path.display()))
+}
+
+mod heroic {
+    use super::{fs, launcher_config_dirs, not_found, parse_error, Deserialize, HashMap, Path, PathBuf, ToryggError};
+
+    #[derive(Deserialize)]
+    struct GogInstalled {
+        installed: Vec<GogGame>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct GogGame {
+        app_name: String,
+        #[serde(rename = "install_path")]
+        install_path: PathBuf,
+    }
+
+    #[derive(Deserialize)]
+    struct LegendaryGame {
+        install_path: PathBuf,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct GameConfig {
+        wine_prefix: Option<PathBuf>,
+    }
+
+    fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Option<T>, ToryggError> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map(Some).map_err(|e| parse_error(path, e))
+    }
+
+    fn install_path(config: &Path, app_name: &str) -> Result<Option<PathBuf>, ToryggError> {
+        if let Some(gog) = read_json::<GogInstalled>(&config.join("gog_store/installed.json"))? {
+            if let Some(game) = gog.installed.into_iter().find(|game| game.app_name == app_name) {
+                return Ok(Some(game.install_path));
+            }
+        }
+
+        let legendary = read_json::<HashMap<String, LegendaryGame>>(&config.join("legendaryConfig/legendary/installed.json"))?;
+        Ok(legendary.and_then(|mut games| games.remove(app_name)).map(|game| game.install_path))
+    }
+
+    /// Find a game in one of Heroic's config directories
+    pub(super) fn locate_in(config: &Path, app_name: &str) -> Result<Option<(PathBuf, Option<PathBuf>)>, ToryggError> {
+        let Some(install_dir) = install_path(config, app_name)? else {
+            return Ok(None)
+        };
+
+        // The game's settings sit next to entries such as `"version": "v0"`
+        let path = config.join("GamesConfig").join(format!("{app_name}.json"));
+        let game = read_json::<HashMap<String, serde_json::Value>>(&path)?
+            .and_then(|mut games| games.remove(app_name))
+            .map(|game| serde_json::from_value::<GameConfig>(game).map_err(|e| parse_error(&path, e)))
+            .transpose()?;
+
+        let prefix = game.and_then(|game| game.wine_prefix)
+            // Proton prefixes keep the wine prefix in a pfx directory
+            .map(|prefix| if prefix.join("pfx/drive_c").is_dir() { prefix.join("pfx") } else { prefix });
+
+        Ok(Some((install_dir, prefix)))
+    }
+
+    /// Find a game from Heroic's `installed.json` and its prefix from `GamesConfig/<app name>.json`
+    pub(super) fn locate(app_name: &str) -> Result<(PathBuf, Option<PathBuf>), ToryggError> {
+        for config in launcher_config_dirs("heroic", "com.heroicgameslauncher.hgl/config/heroic") {
+            if let Some(found) = locate_in(&config, app_name)? {
+                return Ok(found);
+            }
+        }
+
+        Err(not_found("heroic", app_name))
+    }
+}
+
+mod lutris {
+    use super::{fs, not_found, parse_error, Deserialize, Path, PathBuf, ToryggError};
+
+    #[derive(Deserialize)]
+    struct LutrisConfig {
+        game: LutrisGame,
+    }
+
+    #[derive(Deserialize)]
+    struct LutrisGame {
+        exe: Option<PathBuf>,
+        prefix: Option<PathBuf>,
+        working_dir: Option<PathBuf>,
+    }
+
+    /// Whether a config file name is `<slug>.yml` or `<slug>-<timestamp>.yml`
+    fn is_config_for(file_name: &str, slug: &str) -> bool {
+        let Some(stem) = file_name.strip_suffix(".yml") else {
+            return false
+        };
+
+        stem == slug || stem.strip_prefix(slug)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .is_some_and(|rest| rest.chars().all(|char| char.is_ascii_digit()))
+    }
+
+    /// Find a game from its config in one of Lutris' `games` directories
+    pub(super) fn locate_in(dir: &Path, slug: &str) -> Result<Option<(PathBuf, Option<PathBuf>)>, ToryggError> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !is_config_for(&path.file_name().unwrap_or_default().to_string_lossy(), slug) {
+                continue;
+            }
+
+            let text = fs::read_to_string(&path)?;
+            let game = serde_norway::from_str::<LutrisConfig>(&text).map_err(|e| parse_error(&path, e))?.game;
+
+            let install_dir = game.working_dir
+                .or_else(|| Some(game.exe.as_ref()?.parent()?.to_path_buf()))
+                .ok_or_else(|| parse_error(&path, "no exe or working_dir"))?;
+
+            return Ok(Some((install_dir, game.prefix)));
+        }
+
+        Ok(None)
+    }
+
+    /// Find a game from its config in Lutris' `games` directory
+    pub(super) fn locate(slug: &str) -> Result<(PathBuf, Option<PathBuf>), ToryggError> {
+        let dirs = [dirs::config_dir(), dirs::data_dir()].into_iter()
+            .flatten()
+            .map(|dir| dir.join("lutris/games"));
+
+        for dir in dirs.filter(|dir| dir.is_dir()) {
+            if let Some(found) = locate_in(&dir, slug)? {
+                return Ok(found);
+            }
+        }
+
+        Err(not_found("lutris", slug))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, text: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn heroic() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path();
+        write(&config.join("gog_store/installed.json"), r#"{"installed": [
+            {"platform": "windows", "install_path": "/games/Skyrim", "appName": "1711230643", "is_dlc": false}
+        ]}"#);
+        write(&config.join("legendaryConfig/legendary/installed.json"), r#"{
+            "ac82db5035584c7f8a2c548d98c86b2c": {"app_name": "ac82db5035584c7f8a2c548d98c86b2c", "install_path": "/games/Skyrim Epic"}
+        }"#);
+        write(&config.join("GamesConfig/1711230643.json"), r#"{
+            "1711230643": {"winePrefix": "/prefixes/Skyrim", "wineVersion": {"type": "proton"}},
+            "version": "v0",
+            "explicit": true
+        }"#);
+        write(&config.join("GamesConfig/ac82db5035584c7f8a2c548d98c86b2c.json"), r#"{
+            "ac82db5035584c7f8a2c548d98c86b2c": {"autoInstallDxvk": true},
+            "version": "v0"
+        }"#);
+
+        let (install_dir, prefix) = heroic::locate_in(config, "1711230643").unwrap().unwrap();
+        assert_eq!(install_dir, Path::new("/games/Skyrim"));
+        assert_eq!(prefix.as_deref(), Some(Path::new("/prefixes/Skyrim")));
+
+        let (install_dir, prefix) = heroic::locate_in(config, "ac82db5035584c7f8a2c548d98c86b2c").unwrap().unwrap();
+        assert_eq!(install_dir, Path::new("/games/Skyrim Epic"));
+        assert_eq!(prefix, None);
+
+        assert!(heroic::locate_in(config, "other").unwrap().is_none());
+    }
+
+    #[test]
+    fn lutris() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("skyrim-special-edition-1700000000.yml"), "\
+game:
+  exe: /games/Skyrim/SkyrimSELauncher.exe
+  prefix: /prefixes/skyrim
+system:
+  env:
+    DXVK_HUD: fps
+wine:
+  version: lutris-GE-Proton8-26-x86_64
+");
+        write(&dir.path().join("fallout-4.yml"), "game:\n  working_dir: /games/Fallout 4\n");
+        write(&dir.path().join("broken.yml"), "game:\n  prefix: /prefixes/broken\n");
+
+        let (install_dir, prefix) = lutris::locate_in(dir.path(), "skyrim-special-edition").unwrap().unwrap();
+        assert_eq!(install_dir, Path::new("/games/Skyrim"));
+        assert_eq!(prefix.as_deref(), Some(Path::new("/prefixes/skyrim")));
+
+        let (install_dir, prefix) = lutris::locate_in(dir.path(), "fallout-4").unwrap().unwrap();
+        assert_eq!(install_dir, Path::new("/games/Fallout 4"));
+        assert_eq!(prefix, None);
+
+        assert!(lutris::locate_in(dir.path(), "skyrim").unwrap().is_none());
+        assert!(lutris::locate_in(dir.path(), "broken").is_err());
+    }
+}
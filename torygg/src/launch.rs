@@ -0,0 +1,186 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use log::{info, warn};
+use crate::compat::CompatTool;
+use crate::error::ToryggError;
+use crate::install::GameInstall;
+use crate::util::find_case_insensitive_path;
+
+/// A windows program run through proton in a games prefix
+///
+/// This is `<proton> waitforexitandrun <executable>`, the way steam runs games, so proton only
+/// returns once the program and anything it started have exited.
+#[derive(Debug, Clone)]
+pub struct Launch {
+    proton: PathBuf,
+    compat_data: PathBuf,
+    client_install: Option<PathBuf>,
+    appid: Option<usize>,
+    executable: PathBuf,
+    working_dir: Option<PathBuf>,
+    args: Vec<OsString>,
+}
+
+impl Launch {
+    /// Run `executable` with the `proton` script, keeping the prefix in `compat_data`
+    #[must_use]
+    pub fn new(proton: impl Into<PathBuf>, compat_data: impl Into<PathBuf>, executable: impl Into<PathBuf>) -> Launch {
+        Launch {
+            proton: proton.into(),
+            compat_data: compat_data.into(),
+            client_install: None,
+            appid: None,
+            executable: executable.into(),
+            working_dir: None,
+            args: Vec::new(),
+        }
+    }
+
+    /// Run an executable in a games prefix, relative paths are within its install directory
+    ///
+    /// The steam client directory is set when steam was found, proton works without it for
+    /// games from other launchers.
+    ///
+    /// # Errors
+    /// Errors when the executable does not exist or the game does not have a proton prefix
+    pub fn for_game(install: &GameInstall, tool: &CompatTool, executable: &Path) -> Result<Launch, ToryggError> {
+        let install_dir = install.install_dir();
+        let executable = if executable.is_absolute() {
+            executable.to_path_buf()
+        } else {
+            install_dir.join(find_case_insensitive_path(install_dir, executable))
+        };
+        if !executable.is_file() {
+            return Err(ToryggError::ExecutableNotFound(executable));
+        }
+
+        let mut launch = Launch::new(tool.proton(), install.compat_data_dir()?, executable)
+            .appid(install.game().appid());
+        launch.client_install = install.steam_root().map(Path::to_path_buf);
+        Ok(launch)
+    }
+
+    /// The steam installation proton may use, passed as `STEAM_COMPAT_CLIENT_INSTALL_PATH`
+    #[must_use]
+    pub fn client_install_dir(mut self, dir: impl Into<PathBuf>) -> Launch {
+        self.client_install = Some(dir.into());
+        self
+    }
+
+    /// The app proton applies its game specific fixes for
+    #[must_use]
+    pub fn appid(mut self, appid: usize) -> Launch {
+        self.appid = Some(appid);
+        self
+    }
+
+    /// The directory the executable is run in, by default the one containing it
+    #[must_use]
+    pub fn working_dir(mut self, dir: impl Into<PathBuf>) -> Launch {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Add an argument for the executable
+    #[must_use]
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Launch {
+        self.args.push(arg.into());
+        self
+    }
+
+    #[must_use]
+    pub fn executable(&self) -> &Path {
+        &self.executable
+    }
+
+    /// The command that runs the executable
+    #[must_use]
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.proton);
+        command.arg("waitforexitandrun")
+            .arg(&self.executable)
+            .args(&self.args)
+            .env("STEAM_COMPAT_DATA_PATH", &self.compat_data)
+            .env("STEAM_COMPAT_CLIENT_INSTALL_PATH", self.client_install.as_deref().unwrap_or(Path::new("")));
+
+        if let Some(appid) = self.appid {
+            command.env("SteamAppId", appid.to_string())
+                .env("SteamGameId", appid.to_string());
+        }
+
+        if let Some(dir) = self.working_dir.as_deref().or_else(|| self.executable.parent()) {
+            command.current_dir(dir);
+        }
+
+        command
+    }
+
+    /// Run the executable and wait for it to exit
+    ///
+    /// # Errors
+    /// Errors when proton cannot be started or exits unsuccessfully
+    pub fn run(&self) -> Result<(), ToryggError> {
+        info!("Running {} with {}", self.executable.display(), self.proton.display());
+        let status = self.command().status().map_err(|e| {
+            warn!("Failed to start {}: {e}", self.proton.display());
+            ToryggError::FailedToSpawnChild
+        })?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            warn!("{} exited with {status}", self.executable.display());
+            Err(ToryggError::ChildFailed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use super::*;
+
+    /// Write a `proton` script that records how it was run, exiting with `code`
+    fn stub_proton(dir: &Path, code: i32) -> PathBuf {
+        let proton = dir.join("proton");
+        fs::write(&proton, format!(
+            "#!/bin/sh\nprintf '%s\\n' \"$1\" \"$2\" \"$3\" \"$STEAM_COMPAT_DATA_PATH\" \"$STEAM_COMPAT_CLIENT_INSTALL_PATH\" \"$SteamAppId\" \"$PWD\" > \"{}\"\nexit {code}\n",
+            dir.join("ran").display(),
+        )).unwrap();
+        fs::set_permissions(&proton, fs::Permissions::from_mode(0o755)).unwrap();
+        proton
+    }
+
+    #[test]
+    fn run() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let game = dir.join("game");
+        fs::create_dir(&game).unwrap();
+
+        let launch = Launch::new(stub_proton(&dir, 0), dir.join("compatdata"), game.join("skse64_loader.exe"))
+            .client_install_dir(dir.join("steam"))
+            .appid(489_830)
+            .arg("-forcesteamloader");
+        launch.run().unwrap();
+
+        let ran = fs::read_to_string(dir.join("ran")).unwrap();
+        assert_eq!(ran.lines().collect::<Vec<_>>(), [
+            "waitforexitandrun",
+            &game.join("skse64_loader.exe").display().to_string(),
+            "-forcesteamloader",
+            &dir.join("compatdata").display().to_string(),
+            &dir.join("steam").display().to_string(),
+            "489830",
+            &game.display().to_string(),
+        ]);
+
+        let failing = Launch::new(stub_proton(&dir, 1), dir.join("compatdata"), game.join("SkyrimSE.exe"));
+        assert!(matches!(failing.run(), Err(ToryggError::ChildFailed)));
+
+        let missing = Launch::new(dir.join("missing"), dir.join("compatdata"), game.join("SkyrimSE.exe"));
+        assert!(matches!(missing.run(), Err(ToryggError::FailedToSpawnChild)));
+    }
+}
@@ -1,14 +1,39 @@
-mod config;
-pub use config::init_default as init_default;
+mod context;
+pub use context::{init_default, Context, PORTABLE_MARKER};
+mod settings;
+pub use settings::{DeployMethod, GameSettings, Settings};
 mod games;
+pub use games::{PluginListFormat, SteamApp, GAMES};
 mod error;
 pub use error::ToryggError as Error;
 mod profile;
 pub use profile::Profile;
 mod util;
+mod archive;
+pub use archive::{extract as extract_archive, extractors, ArchiveExtractor, ArchiveFormat, ExternalSevenZip, SevenZipExtractor, ZipExtractor};
+mod steam;
+mod install;
+mod compat;
+pub use compat::CompatTool;
+mod launch;
+pub use launch::Launch;
+mod executable;
+pub use executable::Executable;
+mod pe;
+pub use pe::{PeFile, Version};
+mod creation_club;
+pub use creation_club::CreationClub;
+mod skse;
+pub use skse::{ModReport, PluginProblem, PluginReport};
+pub use install::{GameInstall, InstallSource};
+pub use steam::{steam_root, steam_root_candidates, AppManifest, StateFlags, SteamLibrary};
 mod modmanager;
+mod inspect;
+pub use inspect::{inspect_archive, ArchiveProblem, ArchiveReport};
 mod state;
 mod fomod;
+mod bain;
+pub use bain::BainPackage;
 mod existing_directory;
 
 pub use fomod::{
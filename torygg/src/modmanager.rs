@@ -1,13 +1,16 @@
-use std::ffi::OsStr;
 use std::fs;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
 use log::info;
 use tempfile::TempDir;
 use walkdir::WalkDir;
+use crate::context::Context;
 use crate::error::ToryggError;
-use crate::{config, fomod, Torygg};
+use crate::{archive, bain, fomod, Torygg};
+use crate::bain::BainCallback;
 use crate::fomod::FomodCallback;
+use crate::inspect::{is_bain_package, is_data_entry};
+use crate::state::ROOT_FOLDER;
+use crate::games::SteamApp;
 
 /// Get a vec of all installed mods for the given game
 ///
@@ -16,9 +19,9 @@ use crate::fomod::FomodCallback;
 ///
 /// # Panics
 /// Panics when a mods name cannot be determined from its path
-pub fn installed_mods() -> Result<Vec<String>, ToryggError>  {
+pub fn installed_mods(context: &Context, game: &SteamApp) -> Result<Vec<String>, ToryggError>  {
     let mut mods = Vec::new();
-    for entry in fs::read_dir(config::mods_dir()).map_err(ToryggError::IOError)? {
+    for entry in fs::read_dir(context.mods_dir(game)?).map_err(ToryggError::IOError)? {
         let entry = entry.map_err(ToryggError::IOError)?;
         let path = entry.path();
 
@@ -36,43 +39,31 @@ pub fn installed_mods() -> Result<Vec<String>, ToryggError>  {
 ///
 /// # Errors
 /// Errors when installed mods cannot be retrieved
-pub fn mod_installed(mod_name: &String) -> Result<bool, ToryggError> {
-    Ok(installed_mods()?.contains(mod_name))
+pub fn mod_installed(context: &Context, game: &SteamApp, mod_name: &String) -> Result<bool, ToryggError> {
+    Ok(installed_mods(context, game)?.contains(mod_name))
 }
 
 /// Create a new mod with the given name for the given game
 ///
 /// # Errors
 /// Errors when a mod of the same name is already installed
-pub fn create_mod(mod_name: &String) -> Result<(), ToryggError> {
-    if mod_installed(mod_name)? {
+pub fn create_mod(context: &Context, game: &SteamApp, mod_name: &String) -> Result<(), ToryggError> {
+    if mod_installed(context, game, mod_name)? {
         return Err(ToryggError::ModAlreadyExists);
     }
 
-    let _ = config::mods_dir().maybe_create_child_directory(mod_name)?;
+    let _ = context.mods_dir(game)?.maybe_create_child_directory(mod_name)?;
     Ok(())
 }
 
-fn extract_archive(archive: &Path) -> Result<TempDir, ToryggError> {
-    let archive_extract_dir = TempDir::new().unwrap();
-    let archive_extract_path = archive_extract_dir.path();
-
-    // Use p7zip to extract the archive to a temporary directory
-    let mut command = Command::new("7z");
-    command.arg("x");
-    command.arg(format!("-o{}", archive_extract_path.display()));
-    command.arg(archive);
-
-    let status = command.stdout(Stdio::null()).status()?;
-    if !status.success() {
-        return Err(ToryggError::Other("Unable to extract archive".to_owned()));
-    }
-
+pub(crate) fn extract_archive(context: &Context, archive: &Path) -> Result<TempDir, ToryggError> {
+    let archive_extract_dir = TempDir::new()?;
+    archive::extract(&archive::extractors(context.settings()), archive, archive_extract_dir.path())?;
     Ok(archive_extract_dir)
 }
 
-pub(crate) fn install_all(mod_root: &Path, name: &String) -> Result<(), ToryggError> {
-    let install_path = config::mods_dir().maybe_create_child_directory(name)?;
+pub(crate) fn install_all(context: &Context, game: &SteamApp, mod_root: &Path, name: &String) -> Result<(), ToryggError> {
+    let install_path = context.mods_dir(game)?.maybe_create_child_directory(name)?;
 
     let entries = WalkDir::new(mod_root)
         .min_depth(1).into_iter()
@@ -93,65 +84,136 @@ pub(crate) fn install_all(mod_root: &Path, name: &String) -> Result<(), ToryggEr
     Ok(())
 }
 
+/// Lower the root past a lone folder named `Data` or after the archive, both may be there,
+/// eg. `mod_name/Data/actual_mod_stuff`
+fn lower_root(extract_dir: &Path, archive: &Path) -> Result<PathBuf, ToryggError> {
+    let archive_stem = archive.file_stem().unwrap_or_default().to_string_lossy();
+    let mut mod_root = extract_dir.to_owned();
+    loop {
+        let entries = fs::read_dir(&mod_root)?
+            .filter_map(Result::ok)
+            .collect::<Vec<fs::DirEntry>>();
+        let [entry] = entries.as_slice() else {
+            return Ok(mod_root)
+        };
+
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let is_archive_name = unicase::eq(file_name.as_str(), &archive_stem);
+        let is_data = unicase::eq(file_name.as_str(), "Data");
+        if !entry.path().is_dir() || !(is_archive_name || is_data) {
+            return Ok(mod_root)
+        }
+
+        mod_root = entry.path();
+    }
+}
+
+/// Whether a folder looks like the top of a mod: it has an installer, BAIN packages, a `Root` folder
+/// or something the game loads from its data directory
+fn is_mod_root(dir: &Path) -> Result<bool, ToryggError> {
+    let entries = fs::read_dir(dir)?.map(|entry| Ok(entry?.path())).collect::<Result<Vec<_>, ToryggError>>()?;
+    let is_named = |path: &Path, name: &str| path.is_dir() && unicase::eq(path.file_name().unwrap_or_default().to_string_lossy().as_ref(), name);
+
+    Ok(entries.iter().any(|path| is_data_entry(path) || is_named(path, "fomod") || is_named(path, ROOT_FOLDER))
+        || entries.iter().filter(|path| is_bain_package(path)).count() >= 2)
+}
+
+/// Every folder in an extracted archive the mods files may start at, eg. `1K` and `2K` when the
+/// archive has variants side by side
+///
+/// Folders within a root are not looked at. When nothing recognisable is found, the root is lowered
+/// past a lone folder named `Data` or after the archive.
+pub(crate) fn find_mod_roots(extract_dir: &Path, archive: &Path) -> Result<Vec<PathBuf>, ToryggError> {
+    let mut roots = Vec::new();
+    let mut dirs = vec![extract_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        if is_mod_root(&dir)? {
+            roots.push(dir);
+            continue;
+        }
+
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+    }
+
+    if roots.is_empty() {
+        roots.push(lower_root(extract_dir, archive)?);
+    }
+
+    roots.sort();
+    Ok(roots)
+}
+
+/// The `fomod` folder of a mod, when it has one
+pub(crate) fn find_fomod(mod_root: &Path) -> Result<Option<PathBuf>, ToryggError> {
+    for entry in fs::read_dir(mod_root)? {
+        let entry = entry?;
+        if unicase::eq(entry.file_name().to_string_lossy().as_ref(), "fomod") && entry.path().is_dir() {
+            return Ok(Some(entry.path()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Chooses which of several folders in an archive to install, by index, given their paths within the archive
+pub(crate) type ModRootCallback = fn(&[PathBuf]) -> Option<usize>;
+
 /// Install a mod
 ///
+/// When the archive has several folders that look like mods, `mod_root_callback` chooses the one to install.
+/// `bain_callback` chooses the sub-packages of a BAIN installer.
+///
 /// # Errors
 ///  - The archive path does not exist
 ///  - A mod of the same name already exists
-///  - The status of 7z cannot be gotten
-///  - 7z returns unsuccessfully
+///  - The archive cannot be extracted
 ///  - The extracted mods directory cannot be read
+///  - No mod root or BAIN sub-package is chosen
 ///
 /// # Panics
-///  - A temporary directory cannot be created
 ///  - Mod directory cannot be created
 ///  - Copying from temp to final directory fails
-pub fn install_mod(archive: &Path, name: &String, fomod_callback: FomodCallback) -> Result<(), ToryggError> {
+pub fn install_mod(context: &Context, game: &SteamApp, archive: &Path, name: &String, mod_root_callback: ModRootCallback, bain_callback: BainCallback, fomod_callback: FomodCallback) -> Result<(), ToryggError> {
     if !archive.exists() {
         return Err(ToryggError::Other("Archive does not exist!".to_owned()));
     }
 
-    if mod_installed(name)? {
+    if mod_installed(context, game, name)? {
         return Err(ToryggError::ModAlreadyExists)
     }
 
-    let archive_extract_path = extract_archive(archive)?;
+    let archive_extract_path = extract_archive(context, archive)?;
 
-    // lower the `mod_root` if the folder name is 'Data' or the name of the archive
-    // we may need to handle both eg. 'mod_name/Data/actual_mod_stuff'
-    let archive_stem = archive.file_stem().unwrap();
-    let mut mod_root = archive_extract_path.path().to_owned();
-    loop {
-        let entries = fs::read_dir(&mod_root)
-            .map_err(ToryggError::IOError)?
-            .filter_map(Result::ok)
-            .collect::<Vec<fs::DirEntry>>();
-        if entries.len() == 1 {
-            let entry = &entries[0];
-            let file_name = entry.file_name();
-            let path = entry.path();
-
-            let is_archive_name = unicase::eq(&file_name.to_string_lossy(), &archive_stem.to_string_lossy());
-            let is_data = unicase::eq(&file_name.to_string_lossy(), &OsStr::new("Data").to_string_lossy());
-            if path.is_dir() &&  (is_archive_name || is_data) {
-                mod_root = path;
-            }
-        } else {
-            break
-        }
+    let extract_dir = archive_extract_path.path();
+    let roots = find_mod_roots(extract_dir, archive)?;
+    let mod_root = if let [root] = roots.as_slice() {
+        root.clone()
+    } else {
+        let relative_roots = roots.iter()
+            .map(|root| root.strip_prefix(extract_dir).unwrap_or(root).to_path_buf())
+            .collect::<Vec<_>>();
+        let index = mod_root_callback(&relative_roots).ok_or_else(|| ToryggError::Other("no mod root was chosen".to_owned()))?;
+        roots.get(index).cloned().ok_or_else(|| ToryggError::Other("no mod root was chosen".to_owned()))?
+    };
+    info!("installing from {}", mod_root.strip_prefix(extract_dir).unwrap_or(&mod_root).display());
+
+    if let Some(fomod_dir) = find_fomod(&mod_root)? {
+        info!("found fomod");
+        return fomod::fomod_install(context, game, &mod_root, &fomod_dir, name, fomod_callback);
     }
 
-    let entries = fs::read_dir(&mod_root).unwrap()
-        .filter_map(Result::ok)
-        .collect::<Vec<_>>();
-    for entry in entries {
-        if unicase::eq(entry.file_name().to_string_lossy().as_ref(), "fomod"){
-            info!("found fomod");
-            return fomod::fomod_install(&mod_root, &entry.path(), name, fomod_callback);
-        }
+    let packages = bain::packages(&mod_root)?;
+    if !packages.is_empty() {
+        info!("found BAIN installer");
+        return bain::bain_install(context, game, &packages, name, bain_callback);
     }
 
-    install_all(&mod_root, name)
+    install_all(context, game, &mod_root, name)
 }
 
 /// Uninstall a mod for the given game and disables the mod in all profiles
@@ -159,13 +221,47 @@ pub fn install_mod(archive: &Path, name: &String, fomod_callback: FomodCallback)
 /// # Errors
 ///  - Profiles cannot be gotten
 ///  - Removing the files fails
-pub fn uninstall_mod(name: &String) -> Result<(), ToryggError> {
+pub fn uninstall_mod(context: &Context, game: &'static SteamApp, name: &String) -> Result<(), ToryggError> {
     // TODO: check mod is installed
 
-    for mut profile in Torygg::profiles()? {
-        profile.deactivate_mod(name)?;
+    for mut profile in Torygg::game_profiles(context, game)? {
+        profile.deactivate_mod(context, name)?;
     }
 
-    let mod_dir = config::mods_dir().existing_child_directory(name)?;
+    let mod_dir = context.mods_dir(game)?.existing_child_directory(name)?;
     fs::remove_dir_all(mod_dir).map_err(ToryggError::IOError)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create the files in a new directory and find the mod roots in it, relative to the directory
+    fn roots(files: &[&str]) -> Vec<PathBuf> {
+        let dir = tempfile::tempdir().unwrap();
+        for file in files {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        find_mod_roots(dir.path(), Path::new("My Mod.7z")).unwrap().iter()
+            .map(|root| root.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect()
+    }
+
+    #[test]
+    fn mod_roots() {
+        assert_eq!(roots(&["mod.esp", "Optional/other.esp"]), [PathBuf::new()]);
+        assert_eq!(roots(&["My Mod v1.2/Data/Meshes/a.nif", "My Mod v1.2/readme.txt"]), [PathBuf::from("My Mod v1.2/Data")]);
+        assert_eq!(roots(&["1K/textures/a.dds", "2K/textures/a.dds", "Optional/SKSE/Plugins/a.dll", "docs/readme.txt"]), [
+            PathBuf::from("1K"),
+            PathBuf::from("2K"),
+            PathBuf::from("Optional"),
+        ]);
+        assert_eq!(roots(&["Wrapper/fomod/ModuleConfig.xml", "Wrapper/00 Core/textures/a.dds"]), [PathBuf::from("Wrapper")]);
+        assert_eq!(roots(&["Loader/Root/skse64_loader.exe"]), [PathBuf::from("Loader")]);
+        assert_eq!(roots(&["My Mod/Data/readme.txt"]), [PathBuf::from("My Mod/Data")]);
+        assert_eq!(roots(&["Other/readme.txt"]), [PathBuf::new()]);
+    }
+}
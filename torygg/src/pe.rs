@@ -0,0 +1,349 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+use crate::error::ToryggError;
+
+/// A four part file version, eg. `1.6.1170.0`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    pub build: u16,
+}
+
+impl Version {
+    #[must_use]
+    pub const fn new(major: u16, minor: u16, patch: u16, build: u16) -> Version {
+        Version { major, minor, patch, build }
+    }
+
+    /// A version from the most and least significant halves stored in `VS_FIXEDFILEINFO`
+    fn from_parts(ms: u32, ls: u32) -> Version {
+        let high = |part: u32| u16::try_from(part >> 16).unwrap_or_default();
+        let low = |part: u32| u16::try_from(part & 0xFFFF).unwrap_or_default();
+        Version::new(high(ms), low(ms), high(ls), low(ls))
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.major, self.minor, self.patch, self.build)
+    }
+}
+
+const EXPORT_DIRECTORY: usize = 0;
+const RESOURCE_DIRECTORY: usize = 2;
+const RT_VERSION: u32 = 16;
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF_04BD;
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_offset: u32,
+    raw_size: u32,
+}
+
+/// A windows executable or dll, parsed just enough to read its resources and exports
+pub struct PeFile {
+    data: Vec<u8>,
+    sections: Vec<Section>,
+    /// The rva and size of each data directory
+    directories: Vec<(u32, u32)>,
+}
+
+fn invalid(reason: &str) -> ToryggError {
+    ToryggError::InvalidPe(reason.to_owned())
+}
+
+impl PeFile {
+    /// Read and parse a file
+    ///
+    /// # Errors
+    /// Errors when the file cannot be read or is not a PE file
+    pub fn read(path: &Path) -> Result<PeFile, ToryggError> {
+        Self::parse(fs::read(path)?)
+    }
+
+    /// Parse the headers of a PE file
+    ///
+    /// # Errors
+    /// Errors when the data is not a PE file
+    pub fn parse(data: Vec<u8>) -> Result<PeFile, ToryggError> {
+        if data.get(..2) != Some(b"MZ") {
+            return Err(invalid("missing MZ signature"));
+        }
+
+        let pe_offset = read_u32(&data, 0x3C).ok_or_else(|| invalid("truncated DOS header"))? as usize;
+        if data.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+            return Err(invalid("missing PE signature"));
+        }
+
+        let coff = pe_offset + 4;
+        let section_count = read_u16(&data, coff + 2).ok_or_else(|| invalid("truncated COFF header"))?;
+        let optional_size = read_u16(&data, coff + 16).ok_or_else(|| invalid("truncated COFF header"))?;
+
+        let optional = coff + 20;
+        let directories_offset = match read_u16(&data, optional) {
+            Some(0x10B) => optional + 96,
+            Some(0x20B) => optional + 112,
+            _ => return Err(invalid("unknown optional header magic")),
+        };
+
+        let directory_count = read_u32(&data, directories_offset - 4).ok_or_else(|| invalid("truncated optional header"))?;
+        let directories = (0..directory_count as usize)
+            .map(|index| directories_offset + index * 8)
+            .take_while(|offset| offset + 8 <= optional + usize::from(optional_size))
+            .map(|offset| Some((read_u32(&data, offset)?, read_u32(&data, offset + 4)?)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid("truncated data directories"))?;
+
+        let sections = (0..usize::from(section_count))
+            .map(|index| optional + usize::from(optional_size) + index * 40)
+            .map(|offset| Some(Section {
+                virtual_size: read_u32(&data, offset + 8)?,
+                virtual_address: read_u32(&data, offset + 12)?,
+                raw_size: read_u32(&data, offset + 16)?,
+                raw_offset: read_u32(&data, offset + 20)?,
+            }))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid("truncated section table"))?;
+
+        Ok(PeFile { data, sections, directories })
+    }
+
+    /// The file offset of a relative virtual address
+    fn offset(&self, rva: u32) -> Option<usize> {
+        let section = self.sections.iter().find(|section| {
+            rva >= section.virtual_address && rva - section.virtual_address < section.virtual_size.max(section.raw_size)
+        })?;
+
+        let offset = rva - section.virtual_address;
+        (offset < section.raw_size).then(|| section.raw_offset.checked_add(offset))?.map(|offset| offset as usize)
+    }
+
+    /// The data a relative virtual address and size point to
+    fn slice(&self, rva: u32, size: u32) -> Option<&[u8]> {
+        let offset = self.offset(rva)?;
+        self.data.get(offset..offset.checked_add(size as usize)?)
+    }
+
+    fn directory(&self, index: usize) -> Option<(u32, u32)> {
+        self.directories.get(index).copied().filter(|(rva, size)| *rva != 0 && *size != 0)
+    }
+
+    /// Find the first resource of a type, whatever its name and language
+    fn resource(&self, kind: u32) -> Option<&[u8]> {
+        let (rva, size) = self.directory(RESOURCE_DIRECTORY)?;
+        let resources = self.slice(rva, size)?;
+
+        // The tree is type, then name, then language
+        let mut offset = find_resource_entry(resources, 0, Some(kind))?;
+        for _ in 0..2 {
+            offset = find_resource_entry(resources, offset & 0x7FFF_FFFF, None)?;
+        }
+
+        if offset & 0x8000_0000 != 0 {
+            return None;
+        }
+
+        let entry = offset as usize;
+        self.slice(read_u32(resources, entry)?, read_u32(resources, entry + 4)?)
+    }
+
+    /// The names of the exported functions and data
+    #[must_use]
+    pub fn exports(&self) -> Vec<String> {
+        self.export_table().into_iter().map(|(name, _)| name).collect()
+    }
+
+    /// `size` bytes of exported data
+    #[must_use]
+    pub fn export_data(&self, name: &str, size: u32) -> Option<&[u8]> {
+        let (_, rva) = self.export_table().into_iter().find(|(export, _)| export == name)?;
+        self.slice(rva, size)
+    }
+
+    /// Every export by name, along with its rva
+    fn export_table(&self) -> Vec<(String, u32)> {
+        let Some(directory) = self.directory(EXPORT_DIRECTORY).and_then(|(rva, size)| self.slice(rva, size)) else {
+            return Vec::new()
+        };
+
+        let table = |offset: usize| read_u32(directory, offset);
+        let (Some(count), Some(functions), Some(names), Some(ordinals)) = (table(24), table(28), table(32), table(36)) else {
+            return Vec::new()
+        };
+
+        // Each name takes four bytes of the directory, a larger count is corrupt
+        let count = count.min(u32::try_from(directory.len() / 4).unwrap_or(u32::MAX));
+        let element = |table: u32, index: u32, size: u32| table.checked_add(index.checked_mul(size)?);
+
+        (0..count)
+            .filter_map(|index| {
+                let name_rva = read_u32(self.slice(element(names, index, 4)?, 4)?, 0)?;
+                let ordinal = read_u16(self.slice(element(ordinals, index, 2)?, 2)?, 0)?;
+                let rva = read_u32(self.slice(element(functions, u32::from(ordinal), 4)?, 4)?, 0)?;
+                Some((self.string(name_rva)?, rva))
+            })
+            .collect()
+    }
+
+    /// A nul terminated string
+    fn string(&self, rva: u32) -> Option<String> {
+        let offset = self.offset(rva)?;
+        let bytes = self.data.get(offset..)?;
+        let end = bytes.iter().position(|byte| *byte == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+
+    /// Whether the file contains a sequence of bytes anywhere
+    pub(crate) fn contains(&self, needle: &[u8]) -> bool {
+        self.data.windows(needle.len()).any(|window| window == needle)
+    }
+
+    /// The file version from the version resource, when there is one
+    #[must_use]
+    pub fn version(&self) -> Option<Version> {
+        let info = self.resource(RT_VERSION)?;
+
+        // `VS_FIXEDFILEINFO` follows the `VS_VERSION_INFO` key, aligned to four bytes
+        (0..info.len()).step_by(4)
+            .find(|&offset| read_u32(info, offset) == Some(FIXED_FILE_INFO_SIGNATURE))
+            .and_then(|offset| Some(Version::from_parts(read_u32(info, offset + 8)?, read_u32(info, offset + 12)?)))
+    }
+}
+
+/// The `OffsetToData` of an entry in a resource directory, the first entry when `id` is `None`
+fn find_resource_entry(resources: &[u8], directory: u32, id: Option<u32>) -> Option<u32> {
+    let directory = directory as usize;
+    let count = usize::from(read_u16(resources, directory + 12)?) + usize::from(read_u16(resources, directory + 14)?);
+
+    (0..count)
+        .map(|index| directory + 16 + index * 8)
+        .find(|&entry| id.is_none() || read_u32(resources, entry) == id)
+        .and_then(|entry| read_u32(resources, entry + 4))
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_u16(data: &mut Vec<u8>, value: u16) {
+        data.extend(value.to_le_bytes());
+    }
+
+    fn push_u32(data: &mut Vec<u8>, value: u32) {
+        data.extend(value.to_le_bytes());
+    }
+
+    /// A resource directory with a single entry
+    fn push_directory(data: &mut Vec<u8>, id: u32, offset: u32) {
+        data.extend([0; 12]);
+        push_u16(data, 0);
+        push_u16(data, 1);
+        push_u32(data, id);
+        push_u32(data, offset);
+    }
+
+    /// A PE32+ file whose only section holds a version resource
+    fn pe_with_version(version: Version) -> Vec<u8> {
+        const SECTION_RVA: u32 = 0x1000;
+        const SECTION_OFFSET: u32 = 0x200;
+
+        let mut info = Vec::new();
+        push_u16(&mut info, 0);
+        push_u16(&mut info, 52);
+        push_u16(&mut info, 0);
+        info.extend("VS_VERSION_INFO\0".encode_utf16().flat_map(u16::to_le_bytes));
+        info.extend([0; 2]);
+        push_u32(&mut info, FIXED_FILE_INFO_SIGNATURE);
+        push_u32(&mut info, 0x1_0000);
+        push_u32(&mut info, u32::from(version.major) << 16 | u32::from(version.minor));
+        push_u32(&mut info, u32::from(version.patch) << 16 | u32::from(version.build));
+        info.extend([0; 36]);
+
+        let mut resources = Vec::new();
+        push_directory(&mut resources, RT_VERSION, 0x8000_0018);
+        push_directory(&mut resources, 1, 0x8000_0030);
+        push_directory(&mut resources, 0x409, 0x48);
+        push_u32(&mut resources, SECTION_RVA + 0x58);
+        push_u32(&mut resources, u32::try_from(info.len()).unwrap());
+        resources.extend([0; 8]);
+        resources.extend(info);
+
+        let mut data = vec![0; 0x40];
+        data[..2].copy_from_slice(b"MZ");
+        data[0x3C..0x40].copy_from_slice(&0x40_u32.to_le_bytes());
+
+        data.extend(b"PE\0\0");
+        push_u16(&mut data, 0x8664);
+        push_u16(&mut data, 1);
+        data.extend([0; 12]);
+        push_u16(&mut data, 112 + 16 * 8);
+        push_u16(&mut data, 0x22);
+
+        let optional = data.len();
+        push_u16(&mut data, 0x20B);
+        data.resize(optional + 108, 0);
+        push_u32(&mut data, 16);
+        for index in 0..16 {
+            let (rva, size) = if index == RESOURCE_DIRECTORY { (SECTION_RVA, u32::try_from(resources.len()).unwrap()) } else { (0, 0) };
+            push_u32(&mut data, rva);
+            push_u32(&mut data, size);
+        }
+
+        data.extend(b".rsrc\0\0\0");
+        push_u32(&mut data, u32::try_from(resources.len()).unwrap());
+        push_u32(&mut data, SECTION_RVA);
+        push_u32(&mut data, u32::try_from(resources.len()).unwrap());
+        push_u32(&mut data, SECTION_OFFSET);
+        data.extend([0; 16]);
+
+        data.resize(SECTION_OFFSET as usize, 0);
+        data.extend(resources);
+        data
+    }
+
+    #[test]
+    fn version() {
+        let version = Version::new(1, 6, 1170, 0);
+        let pe = PeFile::parse(pe_with_version(version)).unwrap();
+        assert_eq!(pe.version(), Some(version));
+        assert_eq!(version.to_string(), "1.6.1170.0");
+        assert!(Version::new(1, 5, 97, 0) < version);
+
+        assert!(matches!(PeFile::parse(b"not a pe".to_vec()), Err(ToryggError::InvalidPe(_))));
+    }
+
+    #[test]
+    fn corrupt() {
+        let mut data = vec![0; 0x100];
+        // An export directory with a huge count and tables at the end of the address space
+        for (offset, value) in [(24, u32::MAX), (28, 0xFFFF_FFF0), (32, 0xFFFF_FFFE), (36, 0x20)] {
+            data[0x10 + offset..0x14 + offset].copy_from_slice(&u32::to_le_bytes(value));
+        }
+
+        let pe = PeFile {
+            data,
+            sections: vec![
+                Section { virtual_size: 0x100, virtual_address: 0, raw_size: 0x100, raw_offset: 0 },
+                Section { virtual_size: 0x100, virtual_address: 0x1000, raw_size: 0x100, raw_offset: u32::MAX - 0x10 },
+            ],
+            directories: vec![(0x10, 40)],
+        };
+
+        assert!(pe.exports().is_empty());
+        assert_eq!(pe.offset(0x1080), None);
+        assert_eq!(pe.slice(0x1080, 4), None);
+        assert_eq!(pe.version(), None);
+    }
+}
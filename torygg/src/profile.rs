@@ -1,41 +1,38 @@
 use std::fs;
-use anyhow::anyhow;
+use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
+use crate::context::Context;
 use crate::error::ToryggError;
-use crate::{config, modmanager, Torygg};
+use crate::modmanager;
 use crate::existing_directory::ExistingDirectory;
+use crate::games::{SteamApp, SKYRIM_SPECIAL_EDITION};
+
+fn default_game() -> &'static SteamApp {
+    &SKYRIM_SPECIAL_EDITION
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Profile {
     name: String,
     mods: Option<Vec<String>>,
-}
-
-impl std::str::FromStr for Profile {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        for profile in Torygg::profiles().map_err(|e| anyhow!(e))? {
-            if profile.name() == s {
-                return Ok(profile)
-            }
-        }
-
-        Err(anyhow!("Profile not found"))
-    }
+    /// The game the profile belongs to, given by the directory it is stored in
+    #[serde(skip, default = "default_game")]
+    game: &'static SteamApp,
+    #[serde(skip)]
+    dir: PathBuf,
 }
 
 impl Profile {
-    pub(crate) fn new(profile_name: &str) -> Result<Profile, ToryggError> {
-        let config_dir = config::config_dir();
+    pub(crate) fn new(context: &Context, game: &'static SteamApp, profile_name: &str) -> Result<Profile, ToryggError> {
+        let config_dir = context.game_config_dir(game)?;
 
         if config_dir.existing_child_directory(profile_name).is_ok() {
             return Err(ToryggError::ProfileAlreadyExists(profile_name.to_owned()))
         }
 
-        let _profile_dir = config_dir.maybe_create_child_directory(profile_name)?;
+        let dir = config_dir.maybe_create_child_directory(profile_name)?;
 
-        let profile = Profile { name: profile_name.to_string(), mods: None };
+        let profile = Profile { name: profile_name.to_string(), mods: None, game, dir: dir.into() };
         profile.write()?;
         Ok(profile)
     }
@@ -57,19 +54,24 @@ impl Profile {
         &self.name
     }
 
-    pub(crate) fn from_dir(profile_dir: &ExistingDirectory) -> Result<Profile, ToryggError> {
+    #[must_use]
+    pub fn game(&self) -> &'static SteamApp {
+        self.game
+    }
+
+    pub(crate) fn from_dir(game: &'static SteamApp, profile_dir: &ExistingDirectory) -> Result<Profile, ToryggError> {
         let Ok(profile_string) = fs::read_to_string(profile_dir.as_ref().join("profile.toml")) else {
             return Err(ToryggError::Other("failed to read profile.toml".to_owned()));
         };
 
         match toml::from_str::<Profile>(&profile_string) {
-            Ok(profile) => Ok(profile),
+            Ok(profile) => Ok(Profile { game, dir: profile_dir.as_ref().to_path_buf(), ..profile }),
             Err(e) => Err(ToryggError::Other(e.to_string()))
         }
     }
 
-    fn set_mod_enabled(&mut self, mod_name: &String, enabled: bool) -> Result<(), ToryggError> {
-        if !modmanager::mod_installed(mod_name)? {
+    fn set_mod_enabled(&mut self, context: &Context, mod_name: &String, enabled: bool) -> Result<(), ToryggError> {
+        if !modmanager::mod_installed(context, self.game, mod_name)? {
             return Err(ToryggError::Other(String::from("Mod not installed")));
         }
 
@@ -97,12 +99,12 @@ impl Profile {
         Ok(())
     }
 
-    pub(crate) fn activate_mod(&mut self, mod_name: &String) -> Result<(), ToryggError> {
-        self.set_mod_enabled(mod_name, true)
+    pub(crate) fn activate_mod(&mut self, context: &Context, mod_name: &String) -> Result<(), ToryggError> {
+        self.set_mod_enabled(context, mod_name, true)
     }
 
-    pub(crate) fn deactivate_mod(&mut self, mod_name: &String) -> Result<(), ToryggError> {
-        self.set_mod_enabled(mod_name, false)
+    pub(crate) fn deactivate_mod(&mut self, context: &Context, mod_name: &String) -> Result<(), ToryggError> {
+        self.set_mod_enabled(context, mod_name, false)
     }
 
     #[must_use]
@@ -119,7 +121,7 @@ impl Profile {
     }
 
     pub(crate) fn dir(&self) -> Result<ExistingDirectory, ToryggError> {
-        config::config_dir().existing_child_directory(&self.name)
+        ExistingDirectory::try_from(self.dir.clone())
     }
 
     //pub fn mods_dir(&self) -> Result<&PathBuf, ToryggError> {
@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::error::ToryggError;
+use crate::games::{SteamApp, GAMES};
+use crate::steam;
+
+/// How mod files are put into the games directories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployMethod {
+    /// Copy the files, which works everywhere but uses the space twice
+    #[default]
+    Copy,
+    /// Hard link the files, the mods and the game must be on the same filesystem
+    Hardlink,
+    /// Symlink the files
+    Symlink,
+}
+
+impl Display for DeployMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DeployMethod::Copy => "copy",
+            DeployMethod::Hardlink => "hardlink",
+            DeployMethod::Symlink => "symlink",
+        })
+    }
+}
+
+impl FromStr for DeployMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "copy" => Ok(DeployMethod::Copy),
+            "hardlink" => Ok(DeployMethod::Hardlink),
+            "symlink" => Ok(DeployMethod::Symlink),
+            _ => Err(format!("'{s}' is not one of copy, hardlink or symlink")),
+        }
+    }
+}
+
+/// Settings for one game, overriding what its install source finds
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameSettings {
+    install_dir: Option<PathBuf>,
+    prefix: Option<PathBuf>,
+}
+
+impl GameSettings {
+    #[must_use]
+    pub fn install_dir(&self) -> Option<&Path> {
+        self.install_dir.as_deref()
+    }
+
+    #[must_use]
+    pub fn prefix(&self) -> Option<&Path> {
+        self.prefix.as_deref()
+    }
+
+    fn is_empty(&self) -> bool {
+        self == &GameSettings::default()
+    }
+}
+
+/// Torygg's settings, kept in `config.toml` in the config directory, see [`crate::Context::settings`]
+///
+/// Settings are addressed by key, eg. `deploy_method` or `games.skyrimse.prefix`. Environment
+/// variables take precedence over the file, see [`Settings::env_var`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    steam_root: Option<PathBuf>,
+    user_directory: Option<PathBuf>,
+    seven_zip: Option<PathBuf>,
+    deploy_method: Option<DeployMethod>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    games: BTreeMap<String, GameSettings>,
+}
+
+impl Settings {
+    /// Read a `config.toml`, all settings are unset when there is no file
+    pub(crate) fn read(path: &Path) -> Result<Settings, ToryggError> {
+        if !path.is_file() {
+            return Ok(Settings::default());
+        }
+
+        let invalid = |reason: String| ToryggError::InvalidConfig { path: path.to_path_buf(), reason };
+        let settings = toml::from_str::<Settings>(&fs::read_to_string(path)?)
+            .map_err(|e| invalid(e.to_string()))?;
+
+        if let Some(id) = settings.games.keys().find(|id| !GAMES.iter().any(|game| game.id() == id.as_str())) {
+            return Err(invalid(format!("unknown game '{id}' in [games]")));
+        }
+
+        Ok(settings)
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> Result<(), ToryggError> {
+        let text = toml::to_string(self).map_err(|e| ToryggError::Other(e.to_string()))?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Every setting key
+    #[must_use]
+    pub fn keys() -> Vec<String> {
+        let mut keys = ["steam_root", "user_directory", "seven_zip", "deploy_method"].map(str::to_owned).to_vec();
+        for game in GAMES {
+            keys.push(format!("games.{}.install_dir", game.id()));
+            keys.push(format!("games.{}.prefix", game.id()));
+        }
+
+        keys
+    }
+
+    /// The environment variable that overrides a setting
+    #[must_use]
+    pub fn env_var(key: &str) -> Option<&'static str> {
+        match key {
+            "steam_root" => Some("TORYGG_STEAM_ROOT"),
+            "user_directory" => Some("TORYGG_USER_DIRECTORY"),
+            "seven_zip" => Some("TORYGG_7Z"),
+            "deploy_method" => Some("TORYGG_DEPLOY_METHOD"),
+            _ => None,
+        }
+    }
+
+    /// A settings value in the file
+    ///
+    /// # Errors
+    /// Errors when there is no setting with the key
+    pub fn get(&self, key: &str) -> Result<Option<String>, ToryggError> {
+        let path = |path: Option<&PathBuf>| path.map(|path| path.display().to_string());
+        Ok(match key {
+            "steam_root" => path(self.steam_root.as_ref()),
+            "user_directory" => path(self.user_directory.as_ref()),
+            "seven_zip" => path(self.seven_zip.as_ref()),
+            "deploy_method" => self.deploy_method.map(|method| method.to_string()),
+            _ => {
+                let (id, setting) = game_key(key)?;
+                let game = self.games.get(id);
+                match setting {
+                    "install_dir" => path(game.and_then(|game| game.install_dir.as_ref())),
+                    _ => path(game.and_then(|game| game.prefix.as_ref())),
+                }
+            },
+        })
+    }
+
+    /// Change a setting, checking the value is valid
+    ///
+    /// Paths are made absolute and must exist.
+    ///
+    /// # Errors
+    /// Errors when there is no setting with the key or the value is invalid for it
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ToryggError> {
+        let invalid = |reason: String| ToryggError::InvalidSetting { key: key.to_owned(), reason };
+        let directory = || {
+            let path = std::path::absolute(value)?;
+            if path.is_dir() {
+                Ok(path)
+            } else {
+                Err(invalid(format!("{} is not a directory", path.display())))
+            }
+        };
+
+        match key {
+            "steam_root" => {
+                let path = directory()?;
+                if !steam::is_steam_root(&path) {
+                    return Err(invalid(format!("{} does not contain config/libraryfolders.vdf", path.display())));
+                }
+                self.steam_root = Some(path);
+            },
+            "user_directory" => self.user_directory = Some(directory()?),
+            "seven_zip" => {
+                // A bare name is looked up in PATH when extracting
+                let path = PathBuf::from(value);
+                if value.is_empty() || (path.components().count() > 1 && !path.is_file()) {
+                    return Err(invalid(format!("'{value}' is not a file")));
+                }
+                self.seven_zip = Some(if path.components().count() > 1 { std::path::absolute(path)? } else { path });
+            },
+            "deploy_method" => self.deploy_method = Some(value.parse().map_err(invalid)?),
+            _ => {
+                let (id, setting) = game_key(key)?;
+                let path = directory()?;
+                let game = self.games.entry(id.to_owned()).or_default();
+                match setting {
+                    "install_dir" => game.install_dir = Some(path),
+                    _ => game.prefix = Some(path),
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Remove a setting from the file
+    ///
+    /// # Errors
+    /// Errors when there is no setting with the key
+    pub fn unset(&mut self, key: &str) -> Result<(), ToryggError> {
+        match key {
+            "steam_root" => self.steam_root = None,
+            "user_directory" => self.user_directory = None,
+            "seven_zip" => self.seven_zip = None,
+            "deploy_method" => self.deploy_method = None,
+            _ => {
+                let (id, setting) = game_key(key)?;
+                if let Some(game) = self.games.get_mut(id) {
+                    match setting {
+                        "install_dir" => game.install_dir = None,
+                        _ => game.prefix = None,
+                    }
+
+                    if game.is_empty() {
+                        self.games.remove(id);
+                    }
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// The steam installation to use, `TORYGG_STEAM_ROOT` first
+    #[must_use]
+    pub fn steam_root(&self) -> Option<PathBuf> {
+        std::env::var_os("TORYGG_STEAM_ROOT").map(PathBuf::from).or_else(|| self.steam_root.clone())
+    }
+
+    /// The windows user directory in the games prefix, `TORYGG_USER_DIRECTORY` first
+    #[must_use]
+    pub fn user_directory(&self) -> Option<PathBuf> {
+        std::env::var_os("TORYGG_USER_DIRECTORY").map(PathBuf::from).or_else(|| self.user_directory.clone())
+    }
+
+    /// The 7z executable used for archives the built in extractors cannot handle, `TORYGG_7Z` first and `7z` by default
+    #[must_use]
+    pub fn seven_zip(&self) -> PathBuf {
+        std::env::var_os("TORYGG_7Z").map(PathBuf::from)
+            .or_else(|| self.seven_zip.clone())
+            .unwrap_or_else(|| PathBuf::from("7z"))
+    }
+
+    /// How mods are deployed, `TORYGG_DEPLOY_METHOD` first and copying by default
+    ///
+    /// # Errors
+    /// Errors when `TORYGG_DEPLOY_METHOD` is not a deploy method
+    pub fn deploy_method(&self) -> Result<DeployMethod, ToryggError> {
+        match std::env::var("TORYGG_DEPLOY_METHOD") {
+            Ok(value) => value.parse().map_err(|reason| ToryggError::InvalidSetting { key: "TORYGG_DEPLOY_METHOD".to_owned(), reason }),
+            Err(_) => Ok(self.deploy_method.unwrap_or_default()),
+        }
+    }
+
+    /// The settings for a game
+    #[must_use]
+    pub fn game(&self, game: &SteamApp) -> GameSettings {
+        self.games.get(game.id()).cloned().unwrap_or_default()
+    }
+}
+
+/// Split a `games.<id>.<setting>` key
+fn game_key(key: &str) -> Result<(&'static str, &str), ToryggError> {
+    key.strip_prefix("games.")
+        .and_then(|rest| rest.split_once('.'))
+        .filter(|(_, setting)| ["install_dir", "prefix"].contains(setting))
+        .and_then(|(id, setting)| Some((SteamApp::by_id(id)?.id(), setting)))
+        .ok_or_else(|| ToryggError::UnknownSetting(key.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_str().unwrap();
+
+        let mut settings = Settings::default();
+        settings.set("deploy_method", "Hardlink").unwrap();
+        settings.set("games.SkyrimSE.prefix", dir).unwrap();
+        assert_eq!(settings.get("deploy_method").unwrap().as_deref(), Some("hardlink"));
+        assert_eq!(settings.get("games.skyrimse.prefix").unwrap().as_deref(), Some(dir));
+        assert_eq!(settings.get("games.skyrimse.install_dir").unwrap(), None);
+        assert!(Settings::keys().iter().all(|key| settings.get(key).is_ok()));
+
+        assert!(matches!(settings.set("deploy_method", "link"), Err(ToryggError::InvalidSetting { .. })));
+        assert!(matches!(settings.set("games.skyrimse.install_dir", "/nonexistent"), Err(ToryggError::InvalidSetting { .. })));
+        assert!(matches!(settings.set("games.morrowind.prefix", dir), Err(ToryggError::UnknownSetting(_))));
+        assert!(matches!(settings.get("games.skyrimse"), Err(ToryggError::UnknownSetting(_))));
+
+        settings.unset("games.skyrimse.prefix").unwrap();
+        assert!(settings.games.is_empty());
+
+        let text = toml::to_string(&settings).unwrap();
+        assert_eq!(toml::from_str::<Settings>(&text).unwrap(), settings);
+        assert!(toml::from_str::<Settings>("seven_zips = \"7z\"").is_err());
+    }
+}
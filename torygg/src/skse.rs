@@ -0,0 +1,372 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::error::ToryggError;
+use crate::games::{SteamApp, ENDERAL_SPECIAL_EDITION, SKYRIM_SPECIAL_EDITION};
+use crate::install::GameInstall;
+use crate::pe::{read_u32, PeFile, Version};
+use crate::util::find_case_insensitive_path;
+
+/// Where SKSE loads plugins from, within the data directory
+const PLUGINS_DIR: &str = "SKSE/Plugins";
+
+/// The first Anniversary Edition runtime, SKSE only loads plugins with `SKSEPlugin_Version` data from here on
+const ANNIVERSARY_EDITION: Version = Version::new(1, 6, 0, 0);
+/// The runtime that changed the layout of several game structures
+const STRUCTS_CHANGED: Version = Version::new(1, 6, 629, 0);
+
+/// The size of `SKSEPluginVersionData`
+const VERSION_DATA_SIZE: u32 = 848;
+/// `versionIndependence` flags
+const ADDRESS_LIBRARY: u32 = 1 << 0;
+const SIGNATURES: u32 = 1 << 1;
+const STRUCTS_POST_629: u32 = 1 << 2;
+/// `versionIndependenceEx` flags
+const NO_STRUCT_USE: u32 = 1 << 0;
+
+/// The `SKSEPlugin_Version` data an Anniversary Edition plugin exports
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct VersionData {
+    name: String,
+    version_independence: u32,
+    version_independence_ex: u32,
+    compatible_versions: Vec<Version>,
+    script_extender_required: Option<Version>,
+}
+
+impl VersionData {
+    fn parse(data: &[u8]) -> Option<VersionData> {
+        let name = data.get(8..264)?;
+        let name = String::from_utf8_lossy(&name[..name.iter().position(|byte| *byte == 0).unwrap_or(name.len())]).into_owned();
+
+        let compatible_versions = (0..16)
+            .map_while(|index| read_u32(data, 780 + index * 4).filter(|version| *version != 0))
+            .map(unpack_version)
+            .collect();
+
+        Some(VersionData {
+            name,
+            version_independence_ex: read_u32(data, 772)?,
+            version_independence: read_u32(data, 776)?,
+            compatible_versions,
+            script_extender_required: read_u32(data, 844).filter(|version| *version != 0).map(unpack_version),
+        })
+    }
+}
+
+/// A version packed by SKSE's `MAKE_EXE_VERSION_EX`
+fn unpack_version(packed: u32) -> Version {
+    let part = |shift: u32, mask: u32| u16::try_from((packed >> shift) & mask).unwrap_or_default();
+    Version::new(part(24, 0xFF), part(16, 0xFF), part(4, 0xFFF), part(0, 0xF))
+}
+
+/// Why a plugin may fail to load
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginProblem {
+    /// The dll could not be parsed
+    InvalidDll(String),
+    /// Exports neither `SKSEPlugin_Version` nor `SKSEPlugin_Query`
+    NotAPlugin,
+    /// Only exports `SKSEPlugin_Query`, which SKSE no longer calls since the Anniversary Edition
+    SpecialEditionOnly,
+    /// Only exports `SKSEPlugin_Version`, which SKSE for Special Edition does not use
+    AnniversaryEditionOnly,
+    /// Not built for the runtime and not version independent
+    IncompatibleRuntime { runtime: Version, supported: Vec<Version> },
+    /// Uses game structures as they were before 1.6.629
+    OldStructLayout,
+    /// Needs a newer SKSE than is installed
+    ScriptExtenderTooOld { required: Version, installed: Option<Version> },
+    /// Uses Address Library, but it is not installed for the runtime
+    MissingAddressLibrary(String),
+}
+
+impl Display for PluginProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PluginProblem::InvalidDll(reason) => write!(f, "could not be read: {reason}"),
+            PluginProblem::NotAPlugin => write!(f, "exports neither SKSEPlugin_Version nor SKSEPlugin_Query"),
+            PluginProblem::SpecialEditionOnly => write!(f, "built for Special Edition (1.5), it will not load on the Anniversary Edition runtime"),
+            PluginProblem::AnniversaryEditionOnly => write!(f, "built for the Anniversary Edition runtime (1.6), it will not load on Special Edition"),
+            PluginProblem::IncompatibleRuntime { runtime, supported } => {
+                let supported = supported.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+                write!(f, "not built for runtime {runtime}, supports {supported}")
+            },
+            PluginProblem::OldStructLayout => write!(f, "built for runtimes before {STRUCTS_CHANGED}"),
+            PluginProblem::ScriptExtenderTooOld { required, installed: Some(installed) } => write!(f, "requires SKSE {required}, {installed} is installed"),
+            PluginProblem::ScriptExtenderTooOld { required, installed: None } => write!(f, "requires SKSE {required}, which is not installed"),
+            PluginProblem::MissingAddressLibrary(file) => write!(f, "uses Address Library, but {file} is not installed"),
+        }
+    }
+}
+
+/// The result of checking an SKSE plugin against the installed runtime
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginReport {
+    path: PathBuf,
+    name: Option<String>,
+    version: Option<Version>,
+    problems: Vec<PluginProblem>,
+}
+
+impl PluginReport {
+    /// The plugin, relative to its mod
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The name the plugin gives in its `SKSEPlugin_Version` data
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The plugins file version
+    #[must_use]
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    #[must_use]
+    pub fn problems(&self) -> &[PluginProblem] {
+        &self.problems
+    }
+
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// The SKSE plugins of a mod
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModReport {
+    name: String,
+    plugins: Vec<PluginReport>,
+}
+
+impl ModReport {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn plugins(&self) -> &[PluginReport] {
+        &self.plugins
+    }
+
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.plugins.iter().all(PluginReport::is_ok)
+    }
+}
+
+/// What the plugins are checked against
+struct Runtime {
+    game: Version,
+    script_extender: Option<Version>,
+    address_library: String,
+    has_address_library: bool,
+}
+
+/// Whether the games plugins can be checked, which needs SKSE64's plugin version data
+pub(crate) fn is_supported(game: &SteamApp) -> bool {
+    [&SKYRIM_SPECIAL_EDITION, &ENDERAL_SPECIAL_EDITION].contains(&game)
+}
+
+/// The SKSE plugins in a mod or data directory
+fn plugins(dir: &Path) -> Result<Vec<PathBuf>, ToryggError> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let plugins_dir = dir.join(find_case_insensitive_path(dir, PLUGINS_DIR));
+    if !plugins_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut plugins = Vec::new();
+    for entry in fs::read_dir(plugins_dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("dll")) {
+            plugins.push(path);
+        }
+    }
+
+    plugins.sort();
+    Ok(plugins)
+}
+
+fn has_file(dir: &Path, file: &str) -> bool {
+    if !dir.is_dir() {
+        return false;
+    }
+
+    let plugins_dir = dir.join(find_case_insensitive_path(dir, PLUGINS_DIR));
+    plugins_dir.is_dir() && plugins_dir.join(find_case_insensitive_path(&plugins_dir, file)).is_file()
+}
+
+/// Whether a plugin refers to an Address Library database, for plugins that do not say so in their version data
+fn mentions_address_library(pe: &PeFile) -> bool {
+    ["versionlib-", "Plugins/version-"].iter().any(|needle| {
+        let wide = needle.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+        pe.contains(needle.as_bytes()) || pe.contains(&wide)
+    })
+}
+
+/// Check the SKSE plugins of mods against the games runtime, the installed SKSE and Address Library
+///
+/// Address Library counts as installed when any of the mods or the data directory has it.
+pub(crate) fn check_mods(install: &GameInstall, mods_dir: &Path, mods: &[String]) -> Result<Vec<ModReport>, ToryggError> {
+    let mod_dirs = mods.iter()
+        .map(|name| (name, mods_dir.join(name)))
+        .collect::<Vec<_>>();
+
+    let game_version = install.game_version()?;
+    let address_library = if game_version >= ANNIVERSARY_EDITION {
+        format!("versionlib-{}-{}-{}-{}.bin", game_version.major, game_version.minor, game_version.patch, game_version.build)
+    } else {
+        format!("version-{}-{}-{}-{}.bin", game_version.major, game_version.minor, game_version.patch, game_version.build)
+    };
+
+    let data_dir = install.data_dir();
+    let runtime = Runtime {
+        game: game_version,
+        // The loaders version resource is `0.major.minor.beta`, SKSE packs its own version as `major.minor.beta.0`
+        script_extender: install.script_extender_version()?
+            .map(|version| if version.major == 0 { Version::new(version.minor, version.patch, version.build, 0) } else { version }),
+        has_address_library: mod_dirs.iter().map(|(_, dir)| dir.as_path()).chain([data_dir.as_path()])
+            .any(|dir| has_file(dir, &address_library)),
+        address_library,
+    };
+
+    let mut reports = Vec::new();
+    for (name, dir) in &mod_dirs {
+        let plugins = plugins(dir)?.iter()
+            .map(|path| check_plugin(path, dir, &runtime))
+            .collect::<Vec<_>>();
+
+        if !plugins.is_empty() {
+            reports.push(ModReport { name: (*name).clone(), plugins });
+        }
+    }
+
+    Ok(reports)
+}
+
+fn check_plugin(path: &Path, mod_dir: &Path, runtime: &Runtime) -> PluginReport {
+    let mut report = PluginReport {
+        path: path.strip_prefix(mod_dir).unwrap_or(path).to_path_buf(),
+        name: None,
+        version: None,
+        problems: Vec::new(),
+    };
+
+    let pe = match PeFile::read(path) {
+        Ok(pe) => pe,
+        Err(e) => {
+            report.problems.push(PluginProblem::InvalidDll(e.to_string()));
+            return report
+        }
+    };
+
+    let data = pe.export_data("SKSEPlugin_Version", VERSION_DATA_SIZE).and_then(VersionData::parse);
+    let has_query = pe.exports().iter().any(|export| export == "SKSEPlugin_Query");
+    let uses_address_library = data.as_ref().is_some_and(|data| data.version_independence & ADDRESS_LIBRARY != 0)
+        || mentions_address_library(&pe);
+
+    report.version = pe.version();
+    report.problems = problems(data.as_ref(), has_query, uses_address_library, runtime);
+    report.name = data.map(|data| data.name).filter(|name| !name.is_empty());
+    report
+}
+
+fn problems(data: Option<&VersionData>, has_query: bool, uses_address_library: bool, runtime: &Runtime) -> Vec<PluginProblem> {
+    let mut problems = Vec::new();
+    let anniversary_edition = runtime.game >= ANNIVERSARY_EDITION;
+
+    match data {
+        None if !has_query => problems.push(PluginProblem::NotAPlugin),
+        None if anniversary_edition => problems.push(PluginProblem::SpecialEditionOnly),
+        None => {},
+        Some(_) if !anniversary_edition && !has_query => problems.push(PluginProblem::AnniversaryEditionOnly),
+        Some(data) => {
+            let independent = data.version_independence & (ADDRESS_LIBRARY | SIGNATURES) != 0;
+            if anniversary_edition && !independent && !data.compatible_versions.contains(&runtime.game) {
+                problems.push(PluginProblem::IncompatibleRuntime { runtime: runtime.game, supported: data.compatible_versions.clone() });
+            }
+
+            let struct_independent = data.version_independence & STRUCTS_POST_629 != 0 || data.version_independence_ex & NO_STRUCT_USE != 0;
+            if runtime.game >= STRUCTS_CHANGED && !struct_independent {
+                problems.push(PluginProblem::OldStructLayout);
+            }
+
+            if let Some(required) = data.script_extender_required.filter(|required| runtime.script_extender.is_none_or(|installed| installed < *required)) {
+                problems.push(PluginProblem::ScriptExtenderTooOld { required, installed: runtime.script_extender });
+            }
+        },
+    }
+
+    if uses_address_library && !runtime.has_address_library {
+        problems.push(PluginProblem::MissingAddressLibrary(runtime.address_library.clone()));
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack(version: Version) -> u32 {
+        u32::from(version.major) << 24 | u32::from(version.minor) << 16 | u32::from(version.patch) << 4 | u32::from(version.build)
+    }
+
+    fn version_data(independence: u32, compatible: &[Version], required: Option<Version>) -> Vec<u8> {
+        let mut data = vec![0; VERSION_DATA_SIZE as usize];
+        data[..4].copy_from_slice(&1_u32.to_le_bytes());
+        data[8..18].copy_from_slice(b"TestPlugin");
+        data[776..780].copy_from_slice(&independence.to_le_bytes());
+        for (index, version) in compatible.iter().enumerate() {
+            data[780 + index * 4..784 + index * 4].copy_from_slice(&pack(*version).to_le_bytes());
+        }
+        data[844..848].copy_from_slice(&required.map_or(0, pack).to_le_bytes());
+        data
+    }
+
+    fn runtime(game: Version, has_address_library: bool) -> Runtime {
+        Runtime {
+            game,
+            script_extender: Some(Version::new(2, 2, 3, 0)),
+            address_library: "versionlib-1-6-1170-0.bin".to_owned(),
+            has_address_library,
+        }
+    }
+
+    #[test]
+    fn check() {
+        let ae = Version::new(1, 6, 1170, 0);
+        let se = Version::new(1, 5, 97, 0);
+
+        let independent = VersionData::parse(&version_data(ADDRESS_LIBRARY | STRUCTS_POST_629, &[], Some(Version::new(2, 2, 3, 0)))).unwrap();
+        assert_eq!(independent.name, "TestPlugin");
+        assert!(problems(Some(&independent), false, true, &runtime(ae, true)).is_empty());
+        assert_eq!(problems(Some(&independent), false, true, &runtime(ae, false)), [PluginProblem::MissingAddressLibrary("versionlib-1-6-1170-0.bin".to_owned())]);
+        assert_eq!(problems(Some(&independent), false, false, &runtime(se, true)), [PluginProblem::AnniversaryEditionOnly]);
+
+        let pinned = VersionData::parse(&version_data(STRUCTS_POST_629, &[Version::new(1, 6, 640, 0)], Some(Version::new(2, 2, 6, 0)))).unwrap();
+        assert_eq!(problems(Some(&pinned), false, false, &runtime(ae, true)), [
+            PluginProblem::IncompatibleRuntime { runtime: ae, supported: vec![Version::new(1, 6, 640, 0)] },
+            PluginProblem::ScriptExtenderTooOld { required: Version::new(2, 2, 6, 0), installed: Some(Version::new(2, 2, 3, 0)) },
+        ]);
+
+        let old_structs = VersionData::parse(&version_data(ADDRESS_LIBRARY, &[], None)).unwrap();
+        assert_eq!(problems(Some(&old_structs), false, true, &runtime(ae, true)), [PluginProblem::OldStructLayout]);
+
+        assert_eq!(problems(None, true, false, &runtime(ae, true)), [PluginProblem::SpecialEditionOnly]);
+        assert!(problems(None, true, false, &runtime(se, true)).is_empty());
+        assert_eq!(problems(None, false, false, &runtime(se, true)), [PluginProblem::NotAPlugin]);
+    }
+}
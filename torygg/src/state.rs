@@ -1,99 +1,419 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use log::info;
+use std::time::SystemTime;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
-use crate::{config, modmanager};
-use crate::config::data_dir;
+use crate::{executable, inspect, modmanager, skse};
+use crate::compat::CompatTool;
+use crate::context::Context;
 use crate::error::ToryggError;
+use crate::executable::Executable;
 use crate::existing_directory::ExistingDirectory;
+use crate::bain::BainCallback;
 use crate::fomod::FomodCallback;
-use crate::games::SKYRIM_SPECIAL_EDITION;
+use crate::modmanager::ModRootCallback;
+use crate::games::{SteamApp, SKYRIM_SPECIAL_EDITION};
+use crate::inspect::ArchiveReport;
+use crate::install::{GameInstall, InstallSource};
+use crate::launch::Launch;
 use crate::profile::Profile;
+use crate::settings::DeployMethod;
+use crate::skse::ModReport;
 use crate::util::find_case_insensitive_path;
 
-mod serde_profile {
-    use std::fmt::Formatter;
-    use std::str::FromStr;
-    use serde::{de, Deserializer, Serializer};
-    use serde::de::{Visitor};
-    use crate::profile::{Profile};
+/// How the state is stored on disk
+#[derive(Serialize, Deserialize)]
+struct StateFile {
+    /// Missing from states written before torygg managed several games
+    #[serde(default = "default_game_id")]
+    game: String,
+    profile: String,
+    deployed_files: Option<Vec<PathBuf>>,
+    /// Files deployed from mods `Root` folders, relative to the install directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deployed_root_files: Vec<PathBuf>,
+    /// Where each game is installed, by game id, when it is not found through steam
+    #[serde(default)]
+    installs: BTreeMap<String, InstallSource>,
+}
 
-    struct ProfileVisitor;
+fn default_game_id() -> String {
+    SKYRIM_SPECIAL_EDITION.id().to_owned()
+}
 
-    impl<'de> Visitor<'de> for ProfileVisitor {
-        type Value = Profile;
+/// Folder in a mod whose contents are deployed to the games install directory, next to its executable
+pub(crate) const ROOT_FOLDER: &str = "Root";
+
+/// A files size and modification time, used to tell when a tool has changed it
+type FileStamp = (u64, SystemTime);
+
+/// Every entry in a directory, parents first, with a stamp for each file
+fn snapshot(dir: &Path) -> Vec<(PathBuf, Option<FileStamp>)> {
+    WalkDir::new(dir).min_depth(1).into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let relative_path = entry.path().strip_prefix(dir).ok()?.to_path_buf();
+            let metadata = fs::metadata(entry.path()).ok()?;
+            let stamp = metadata.is_file()
+                .then(|| (metadata.len(), metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)));
+            Some((relative_path, stamp))
+        })
+        .collect()
+}
 
-        fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
-            write!(formatter, "name of a profile")
-        }
+/// Replace deployed links with copies of the files they link to, so that changing them leaves the mods alone
+fn copy_links(target: &Path, deployed: &[PathBuf]) -> Result<(), ToryggError> {
+    for relative_path in deployed {
+        let path = target.join(relative_path);
+        let Ok(metadata) = path.symlink_metadata() else {
+            continue
+        };
 
-        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: de::Error {
-            Profile::from_str(v).map_err(|_| de::Error::invalid_value(de::Unexpected::Str(v), &self))
+        if metadata.is_symlink() || (metadata.is_file() && metadata.nlink() > 1) {
+            // Copied beside the link first so that the link is replaced in one step
+            let copy = path.with_file_name(format!(".{}.torygg", path.file_name().unwrap_or_default().to_string_lossy()));
+            fs::copy(&path, &copy)?;
+            fs::rename(&copy, &path)?;
         }
     }
 
-    pub fn serialize<S>(profile: &Profile, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer  {
-        serializer.serialize_str(profile.name())
-    }
+    Ok(())
+}
+
+/// Copy the files a tool created or changed in the data directory into its output mod
+///
+/// `deployed` is the files deployed to the data directory, new files are added to it.
+fn collect_files(data_dir: &Path, before: &HashMap<PathBuf, Option<FileStamp>>, mod_dir: &Path, output_mod: &str, deployed: &mut Vec<PathBuf>) -> Result<(), ToryggError> {
+    for (relative_path, stamp) in snapshot(data_dir) {
+        if before.get(&relative_path) == Some(&stamp) {
+            continue;
+        }
+
+        let managed = deployed.contains(&relative_path);
+        if before.contains_key(&relative_path) && !managed {
+            warn!("{} was changed but is not a mod file, leaving it in place", relative_path.display());
+            continue;
+        }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Profile, D::Error> where D: Deserializer<'de> {
-        deserializer.deserialize_str(ProfileVisitor)
+        let to_path = mod_dir.join(&relative_path);
+        if stamp.is_some() {
+            info!("{} -> {output_mod}", relative_path.display());
+            if let Some(parent) = to_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(data_dir.join(&relative_path), to_path)?;
+        } else {
+            fs::create_dir_all(to_path)?;
+        }
+
+        if !managed {
+            deployed.push(relative_path);
+        }
     }
+
+    Ok(())
 }
 
 /// Torygg's persistent state
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct ToryggState {
-    //game: &'static SteamApp,
-    #[serde(with = "serde_profile")]
+    context: Context,
+    game: &'static SteamApp,
     profile: Profile,
-    deployed_files: Option<Vec<PathBuf>>
+    deployed_files: Option<Vec<PathBuf>>,
+    deployed_root_files: Vec<PathBuf>,
+    installs: BTreeMap<String, InstallSource>,
 }
 
-impl Default for ToryggState {
-    fn default() -> Self {
-        Self::read_or_new()
+impl ToryggState {
+    #[must_use]
+    pub fn context(&self) -> &Context {
+        &self.context
     }
-}
 
-impl ToryggState {
-    // fn game(&self) -> &'static SteamApp {
-    //     self.game
-    // }
+    /// The context, to change the settings
+    #[must_use]
+    pub fn context_mut(&mut self) -> &mut Context {
+        &mut self.context
+    }
+
+    #[must_use]
+    pub fn game(&self) -> &'static SteamApp {
+        self.game
+    }
 
+    /// Create a new state for Skyrim Special Edition using its first profile and write it to disk
+    ///
+    /// # Panics
+    /// Panics when the state cannot be written or no profile can be found or created
     #[must_use]
-    pub fn new() -> ToryggState {
+    pub fn new(context: Context) -> ToryggState {
+        let game = &SKYRIM_SPECIAL_EDITION;
         let state = ToryggState {
-            profile: Self::default_profile(),
+            profile: Self::default_profile(&context, game),
+            context,
+            game,
             deployed_files: None,
+            deployed_root_files: Vec::new(),
+            installs: BTreeMap::new(),
         };
         state.write().unwrap();
         state
     }
 
-    fn default_profile() -> Profile {
-        Self::profiles().unwrap().first().unwrap().clone()
+    fn default_profile(context: &Context, game: &'static SteamApp) -> Profile {
+        Self::game_profiles(context, game).unwrap().first().unwrap().clone()
     }
 
+    /// Switch to managing another game, using its first profile
+    ///
+    /// # Errors
+    /// Errors when deployed or the state cannot be written
+    pub fn set_game(&mut self, game: &'static SteamApp) -> Result<(), ToryggError> {
+        if self.deployed() {
+            return Err(ToryggError::IsDeployed)
+        }
+
+        self.game = game;
+        self.profile = Self::default_profile(&self.context, game);
+        self.write()?;
+        Ok(())
+    }
+
+    /// Where the current game is installed
+    #[must_use]
+    pub fn install_source(&self) -> InstallSource {
+        self.installs.get(self.game.id()).cloned().unwrap_or_default()
+    }
+
+    /// Change where the current game is installed
+    ///
+    /// # Errors
+    /// Errors when deployed, the game cannot be found using the source or the state cannot be written
+    pub fn set_install_source(&mut self, source: InstallSource) -> Result<(), ToryggError> {
+        if self.deployed() {
+            return Err(ToryggError::IsDeployed)
+        }
+
+        GameInstall::locate(&self.context, self.game, &source)?;
+        if source == InstallSource::Steam {
+            self.installs.remove(self.game.id());
+        } else {
+            self.installs.insert(self.game.id().to_owned(), source);
+        }
+
+        self.write()?;
+        Ok(())
+    }
+
+    /// Locate the current games installation
+    ///
+    /// # Errors
+    /// Errors when the game cannot be found
+    pub fn install(&self) -> Result<GameInstall, ToryggError> {
+        GameInstall::locate(&self.context, self.game, &self.install_source())
+    }
+
+    fn compat_tool(&self, name: Option<&str>) -> Result<CompatTool, ToryggError> {
+        let steam_root = self.context.steam_root()?;
+        match name {
+            Some(name) => CompatTool::find(&steam_root, name),
+            None => self.game.compat_tool(&steam_root),
+        }
+    }
+
+    /// Prepare to run the game, or its script extender, in its prefix
+    ///
+    /// The compatibility tool steam has configured for the game is used unless `tool` names another.
+    ///
+    /// # Errors
+    /// Errors when the game, its executable, its prefix or the compatibility tool cannot be found
+    pub fn launch(&self, script_extender: bool, tool: Option<&str>) -> Result<Launch, ToryggError> {
+        let executable = if script_extender {
+            self.game.script_extender_loader()
+        } else {
+            self.game.executable()
+        };
+
+        Launch::for_game(&self.install()?, &self.compat_tool(tool)?, Path::new(executable))
+    }
+
+    /// Check the SKSE plugins of the active mods against the games runtime, the installed SKSE and Address Library
+    ///
+    /// Only mods with plugins are reported.
+    ///
+    /// # Errors
+    /// Errors when the game is not Skyrim Special Edition or Enderal, or its version cannot be read
+    pub fn check_plugins(&self) -> Result<Vec<ModReport>, ToryggError> {
+        if !skse::is_supported(self.game) {
+            return Err(ToryggError::ChecksNotSupported(self.game.name()));
+        }
+
+        let mods = self.profile.enabled_mods().cloned().unwrap_or_default();
+        skse::check_mods(&self.install()?, self.context.mods_dir(self.game)?.as_ref(), &mods)
+    }
+
+    /// Get the modding tools configured for the current game
+    ///
+    /// # Errors
+    /// Errors when `executables.toml` cannot be read or parsed
+    pub fn executables(&self) -> Result<Vec<Executable>, ToryggError> {
+        executable::read(&self.context, self.game)
+    }
+
+    /// Find a modding tool of the current game by name
+    ///
+    /// # Errors
+    /// Errors when the executables cannot be read or none has the name
+    pub fn find_executable(&self, name: &str) -> Result<Executable, ToryggError> {
+        self.executables()?.into_iter()
+            .find(|executable| executable.name() == name)
+            .ok_or_else(|| ToryggError::UnknownExecutable(name.to_owned()))
+    }
+
+    /// Add a modding tool to the current game
+    ///
+    /// # Errors
+    /// Errors when a tool of the same name exists or the executables cannot be written
+    pub fn add_executable(&self, executable: Executable) -> Result<(), ToryggError> {
+        let mut executables = self.executables()?;
+        if executables.iter().any(|existing| existing.name() == executable.name()) {
+            return Err(ToryggError::ExecutableAlreadyExists(executable.name().to_owned()));
+        }
+
+        executables.push(executable);
+        executable::write(&self.context, self.game, executables)
+    }
+
+    /// Remove a modding tool from the current game
+    ///
+    /// # Errors
+    /// Errors when there is no tool with the name or the executables cannot be written
+    pub fn remove_executable(&self, name: &str) -> Result<(), ToryggError> {
+        let mut executables = self.executables()?;
+        let count = executables.len();
+        executables.retain(|executable| executable.name() != name);
+        if executables.len() == count {
+            return Err(ToryggError::UnknownExecutable(name.to_owned()));
+        }
+
+        executable::write(&self.context, self.game, executables)
+    }
+
+    /// Run a modding tool in the games prefix with the mods deployed
+    ///
+    /// The mods are deployed for the run when they are not already. Files the tool creates in the data
+    /// directory, or changes after they were deployed, are copied into its output mod, which is then activated.
+    /// Mod files deployed as links are copied first, so that the tool cannot change the mods through them.
+    ///
+    /// # Errors
+    /// Errors when the tool cannot be found or run, deploying fails or the output cannot be collected
+    pub fn run_executable(&mut self, name: &str, tool: Option<&str>) -> Result<(), ToryggError> {
+        let executable = self.find_executable(name)?;
+        let install = self.install()?;
+
+        let mut launch = Launch::for_game(&install, &self.compat_tool(tool)?, executable.path())?;
+        if let Some(dir) = executable.working_dir() {
+            launch = launch.working_dir(install.install_dir().join(dir));
+        }
+        for arg in executable.args() {
+            launch = launch.arg(arg);
+        }
+
+        // Only undeploy what this run deployed
+        let deploy = !self.deployed();
+        if deploy {
+            self.deploy()?;
+        }
+
+        let result = self.run_and_collect(&launch, &install.data_dir(), executable.output_mod());
+
+        // Nothing is deployed when there are no mods or output
+        let undeployed = if deploy && self.deployed() { self.undeploy() } else { Ok(()) };
+        result.and(undeployed)
+    }
+
+    /// Run a tool with the mods deployed and collect what it writes into its output mod
+    fn run_and_collect(&mut self, launch: &Launch, data_dir: &Path, output_mod: Option<&str>) -> Result<(), ToryggError> {
+        // Hard and symbolic links would let the tool change the mods files in place
+        if output_mod.is_some() {
+            copy_links(data_dir, self.deployed_files.as_deref().unwrap_or_default())?;
+        }
+
+        let before = snapshot(data_dir).into_iter().collect::<HashMap<_, _>>();
+        let result = launch.run();
+
+        let collected = output_mod.map_or(Ok(()), |output_mod| self.collect_output(data_dir, &before, output_mod));
+        result.and(collected)
+    }
+
+    /// Copy new and changed files in the data directory into a mod and track them as deployed
+    fn collect_output(&mut self, data_dir: &Path, before: &HashMap<PathBuf, Option<FileStamp>>, output_mod: &str) -> Result<(), ToryggError> {
+        let mod_dir = self.context.mods_dir(self.game)?.maybe_create_child_directory(output_mod)?;
+        let mut deployed = self.deployed_files.clone().unwrap_or_default();
+
+        // What was collected is recorded as deployed even when collecting fails part way, so that it is undeployed
+        let collected = collect_files(data_dir, before, mod_dir.as_ref(), output_mod, &mut deployed);
+        self.deployed_files = (!deployed.is_empty() || !self.deployed_root_files.is_empty()).then_some(deployed);
+        self.write()?;
+        collected?;
+
+        if !self.profile.mod_enabled(&output_mod.to_owned()) {
+            self.profile.activate_mod(&self.context, &output_mod.to_owned())?;
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
     pub fn deployed(&self) -> bool {
         self.deployed_files.is_some()
     }
 
-    pub fn mods() -> Result<Vec<String>, ToryggError> {
-        modmanager::installed_mods()
+    /// Get all installed mods
+    ///
+    /// # Errors
+    /// Errors when the mod directory cannot be read
+    pub fn mods(&self) -> Result<Vec<String>, ToryggError> {
+        modmanager::installed_mods(&self.context, self.game)
     }
 
-    pub fn install_mod(archive: &Path, name: &String, fomod_callback: FomodCallback) -> Result<(), ToryggError> {
-        modmanager::install_mod(archive, name, fomod_callback)
+    /// Install a mod from an archive
+    ///
+    /// `mod_root_callback` chooses the folder to install when the archive has several that look like mods,
+    /// such as texture resolutions side by side. `bain_callback` chooses the sub-packages of a BAIN installer.
+    ///
+    /// # Errors
+    /// Errors when the archive cannot be extracted or installed, or no folder or sub-package is chosen
+    pub fn install_mod(&self, archive: &Path, name: &String, mod_root_callback: ModRootCallback, bain_callback: BainCallback, fomod_callback: FomodCallback) -> Result<(), ToryggError> {
+        modmanager::install_mod(&self.context, self.game, archive, name, mod_root_callback, bain_callback, fomod_callback)
     }
 
-    pub fn uninstall_mod(name: &String) -> Result<(), ToryggError> {
-        modmanager::uninstall_mod(name)
+    /// Report what an archive contains, as installing it would find it
+    ///
+    /// # Errors
+    /// Errors when the archive cannot be extracted or read
+    pub fn inspect_archive(&self, archive: &Path) -> Result<ArchiveReport, ToryggError> {
+        inspect::inspect_archive(&self.context, archive)
     }
 
-    pub fn create_mod(mod_name: &String) -> Result<(), ToryggError> {
-        modmanager::create_mod(mod_name)
+    /// Uninstall a mod, disabling it in every profile
+    ///
+    /// # Errors
+    /// Errors when profiles cannot be updated or the mod cannot be removed
+    pub fn uninstall_mod(&self, name: &String) -> Result<(), ToryggError> {
+        modmanager::uninstall_mod(&self.context, self.game, name)
+    }
+
+    /// Create a new, empty, mod
+    ///
+    /// # Errors
+    /// Errors when a mod of the same name is already installed
+    pub fn create_mod(&self, mod_name: &String) -> Result<(), ToryggError> {
+        modmanager::create_mod(&self.context, self.game, mod_name)
     }
 
     #[must_use]
@@ -106,46 +426,75 @@ impl ToryggState {
         self.profile().mod_enabled(mod_name)
     }
 
+    /// Activate a mod in the current profile
+    ///
+    /// # Errors
+    /// Errors when deployed or the mod is not installed
     pub fn activate_mod(&mut self, name: &String) -> Result<(), ToryggError> {
         if self.deployed() {
             return Err(ToryggError::IsDeployed)
         }
 
-        self.profile.activate_mod(name)
+        self.profile.activate_mod(&self.context, name)
     }
 
+    /// Deactivate a mod in the current profile
+    ///
+    /// # Errors
+    /// Errors when deployed or the mod is not installed
     pub fn deactivate_mod(&mut self, name: &String) -> Result<(), ToryggError> {
         if self.deployed() {
             return Err(ToryggError::IsDeployed)
         }
 
-        self.profile.deactivate_mod(name)
+        self.profile.deactivate_mod(&self.context, name)
+    }
+
+    /// Get all profiles of the current game, creating a default profile if there are none
+    ///
+    /// # Errors
+    /// Errors when the config directory cannot be read
+    ///
+    /// # Panics
+    /// Panics when the default profile cannot be created
+    pub fn profiles(&self) -> Result<Vec<Profile>, ToryggError> {
+        Self::game_profiles(&self.context, self.game)
     }
 
-    pub fn profiles() -> Result<Vec<Profile>, ToryggError> {
-        let profs = fs::read_dir(config::config_dir())?
+    pub(crate) fn game_profiles(context: &Context, game: &'static SteamApp) -> Result<Vec<Profile>, ToryggError> {
+        let profs = fs::read_dir(context.game_config_dir(game)?)?
             .filter_map(|e| Some(e.ok()?.path()))
             .filter_map(|e| ExistingDirectory::try_from(e).ok())
-            .filter_map(|e| Profile::from_dir(&e).ok())
+            .filter_map(|e| Profile::from_dir(game, &e).ok())
             .collect::<Vec<_>>();
 
         if profs.is_empty() {
-            Profile::new("Default").unwrap();
-            return Self::profiles()
+            Profile::new(context, game, "Default").unwrap();
+            return Self::game_profiles(context, game)
         }
 
         Ok(profs)
     }
 
+    /// Find a profile of the current game by name
+    ///
+    /// # Errors
+    /// Errors when the profiles cannot be read or there is no profile with the name
+    pub fn find_profile(&self, name: &str) -> Result<Profile, ToryggError> {
+        self.profiles()?.into_iter()
+            .find(|profile| profile.name() == name)
+            .ok_or_else(|| ToryggError::ProfileNotFound(name.to_owned()))
+    }
+
     #[must_use]
     pub fn profile(&self) -> &Profile {
         &self.profile
     }
 
-    fn profile_mut(&mut self) -> &mut Profile {
-        &mut self.profile
-    }
-
+    /// Switch to another profile
+    ///
+    /// # Errors
+    /// Errors when deployed or the state cannot be written
     pub fn set_profile(&mut self, profile: Profile) -> Result<(), ToryggError> {
         if self.deployed() {
             return Err(ToryggError::IsDeployed)
@@ -156,10 +505,18 @@ impl ToryggState {
         Ok(())
     }
 
-    pub fn create_profile(name: &str) -> Result<Profile, ToryggError> {
-        Profile::new(name)
+    /// Create a new profile for the current game
+    ///
+    /// # Errors
+    /// Errors when a profile of the same name already exists
+    pub fn create_profile(&self, name: &str) -> Result<Profile, ToryggError> {
+        Profile::new(&self.context, self.game, name)
     }
 
+    /// Delete a profile, switching to the default profile if it is the current one
+    ///
+    /// # Errors
+    /// Errors when the profile is current and deployed, or its directory cannot be removed
     pub fn delete_profile(&mut self, profile: &Profile) -> Result<(), ToryggError> {
         if profile == self.profile() && self.deployed() {
             return Err(ToryggError::IsDeployed)
@@ -168,30 +525,71 @@ impl ToryggState {
         fs::remove_dir_all(profile.dir()?)?;
 
         if profile == self.profile() {
-            self.profile = Self::default_profile();
+            self.profile = Self::default_profile(&self.context, self.game);
         }
 
         Ok(())
     }
 
-    fn path() -> PathBuf {
-        data_dir().as_ref().join(".toryggstate.toml")
+    fn path(context: &Context) -> PathBuf {
+        context.data_dir().join(".toryggstate.toml")
     }
 
-    fn read() -> Result<ToryggState, ToryggError> {
-        let s = fs::read_to_string(Self::path())?;
-        toml::from_str::<ToryggState>(&s).map_err(|_| ToryggError::Other("Failed to parse state toml".to_owned()))
+    fn read(context: &Context) -> Result<ToryggState, ToryggError> {
+        let path = Self::path(context);
+        let s = fs::read_to_string(&path)?;
+        let file = toml::from_str::<StateFile>(&s).map_err(|e| ToryggError::InvalidConfig { path, reason: e.to_string() })?;
+
+        let game = SteamApp::by_id(&file.game).ok_or(ToryggError::GameNotFound(file.game))?;
+        let profile = Self::game_profiles(context, game)?.into_iter()
+            .find(|profile| profile.name() == file.profile)
+            .unwrap_or_else(|| Self::default_profile(context, game));
+
+        Ok(ToryggState {
+            context: context.clone(),
+            game,
+            profile,
+            deployed_files: file.deployed_files,
+            deployed_root_files: file.deployed_root_files,
+            installs: file.installs,
+        })
     }
 
     fn write(&self) -> Result<(), std::io::Error> {
-        fs::write(Self::path(), toml::to_string(self).unwrap())
+        let file = StateFile {
+            game: self.game.id().to_owned(),
+            profile: self.profile.name().to_owned(),
+            deployed_files: self.deployed_files.clone(),
+            deployed_root_files: self.deployed_root_files.clone(),
+            installs: self.installs.clone(),
+        };
+        fs::write(Self::path(&self.context), toml::to_string(&file).unwrap())
     }
 
-    #[must_use]
-    pub fn read_or_new() -> ToryggState {
-        ToryggState::read().unwrap_or_else(|_| ToryggState::new())
+    /// Read the state kept in the contexts data directory, or create a new one if there is none
+    ///
+    /// # Errors
+    /// Errors when the state cannot be read or is invalid, it is left alone so the deployed files are not
+    /// forgotten
+    ///
+    /// # Panics
+    /// Panics when a new state cannot be written or no profile can be found or created
+    pub fn read_or_new(context: Context) -> Result<ToryggState, ToryggError> {
+        if Self::path(&context).exists() {
+            ToryggState::read(&context)
+        } else {
+            Ok(ToryggState::new(context))
+        }
     }
 
+    /// Copy the active mods into the games data directory, and the contents of their `Root` folders
+    /// into the install directory
+    ///
+    /// # Errors
+    /// Errors when already deployed, the game cannot be found, steam is updating the game or copying the files fails
+    ///
+    /// # Panics
+    /// Panics when an enabled mod is not installed
     pub fn deploy(&mut self) -> Result<(), ToryggError> {
         if self.deployed() {
             return Err(ToryggError::Other("Already Deployed".to_owned()))
@@ -202,96 +600,343 @@ impl ToryggState {
             return Ok(())
         };
 
-        // Take note of pre-existing files
-        let data_path = SKYRIM_SPECIAL_EDITION.install_dir().unwrap().join("Data");
-        let unmanaged_files = WalkDir::new(&data_path).min_depth(1).into_iter()
-            .filter_map(|entry| Some(entry.ok()?.path().to_owned()))
-            .collect::<Vec<_>>();
-
-        let backup_dir = data_dir().maybe_create_child_directory("Backup")?;
+        // Steam may replace or remove files while it is updating
+        let install = self.install()?;
+        if install.is_updating()? {
+            return Err(ToryggError::GameUpdating)
+        }
 
-        let mut result  = Vec::new();
-        for m in mods {
-            let dir = config::mods_dir().existing_child_directory(m)
+        let method = self.context.settings().deploy_method()?;
+        let data_path = install.data_dir();
+        let game_data_dir = self.context.game_data_dir(self.game)?;
+        let mods_dir = self.context.mods_dir(self.game)?;
+        let backup_dir = game_data_dir.maybe_create_child_directory("Backup")?;
+        let root_backup_dir = game_data_dir.maybe_create_child_directory("RootBackup")?;
+
+        let mut result = Vec::new();
+        let mut root_result = Vec::new();
+        let deployed = mods.iter().try_for_each(|m| {
+            let dir = mods_dir.existing_child_directory(m)
                 .expect("mod directory does not exist");
 
-            for entry in WalkDir::new(&dir).min_depth(1) {
-                let entry = entry.unwrap();
-                let path = entry.path();
-
-                let relative_path = path.strip_prefix(&dir).unwrap();
-                let to_relative_path = find_case_insensitive_path(&data_path, relative_path);
-                let to_path = data_path.join(&to_relative_path);
-
-                if path.is_dir() {
-                    if to_path.is_dir() {
-                        continue;
-                    }
-
-                    fs::create_dir(&to_path)?;
-                    result.push(to_relative_path);
-                } else {
-                    info!("{} -> {}", relative_path.display(), to_relative_path.display());
-
-                    if to_path.exists() && unmanaged_files.contains(&to_path) {
-                        let backup_path = backup_dir.as_ref().join(&to_relative_path);
-                        for dir in to_relative_path.parent().unwrap() {
-                            let _ = backup_dir.maybe_create_child_directory(dir)?;
-                        }
-                        fs::rename(&to_path, &backup_path)?;
-                    }
-
-                    fs::copy(path, &to_path)?;
-                    if !result.contains(&to_relative_path) {
-                        result.push(to_relative_path);
-                    }
-                }
+            let root_dir = root_folder(dir.as_ref());
+            deploy_files(dir.as_ref(), &data_path, &backup_dir, root_dir.as_deref(), method, &mut result)?;
+            if let Some(root_dir) = root_dir {
+                deploy_files(&root_dir, install.install_dir(), &root_backup_dir, None, method, &mut root_result)?;
             }
-        }
 
-        if !result.is_empty() {
+            Ok::<_, ToryggError>(())
+        });
+
+        // Files deployed before a failure are kept track of too, so undeploying removes them
+        if !result.is_empty() || !root_result.is_empty() {
             self.deployed_files = Some(result);
+            self.deployed_root_files = root_result;
             self.write()?;
         }
 
-        Ok(())
+        deployed
     }
 
+    /// Remove deployed mod files and restore any backed up files
+    ///
+    /// # Errors
+    /// Errors when not deployed or removing the files fails
+    ///
+    /// # Panics
+    /// Panics when the state cannot be written or backed up files cannot be restored
     pub fn undeploy(&mut self) -> Result<(), ToryggError> {
         let Some(deployed) = &self.deployed_files else {
             return Err(ToryggError::IsNotDeployed)
         };
 
         // Remove mod files
-        let data_path = SKYRIM_SPECIAL_EDITION.install_dir()?.join("Data");
-        for relative_path in deployed.iter().rev() {
-            let path = data_path.join(relative_path);
-            if path.is_dir() {
-                fs::remove_dir(path)?;
-            } else {
-                fs::remove_file(path)?;
-            }
-        }
+        let install = self.install()?;
+        let data_path = install.data_dir();
+        remove_files(&data_path, deployed)?;
+        remove_files(install.install_dir(), &self.deployed_root_files)?;
 
         self.deployed_files = None;
+        self.deployed_root_files = Vec::new();
         self.write().unwrap();
 
         // Restore any backed up files
-        let backup_dir = data_dir().maybe_create_child_directory("Backup")?;
-        for entry in WalkDir::new(&backup_dir).min_depth(1).contents_first(true) {
-            let entry = entry.unwrap();
-            let path = entry.path();
-            let relative_path = path.strip_prefix(&backup_dir).unwrap();
-            let to_path = data_path.join(relative_path);
-
-            if path.is_file() {
-                info!("{}", relative_path.display());
-                fs::rename(path, to_path).unwrap();
-            } else {
-                fs::remove_dir(path).unwrap();
+        let game_data_dir = self.context.game_data_dir(self.game)?;
+        restore_backups(&game_data_dir.maybe_create_child_directory("Backup")?, &data_path);
+        restore_backups(&game_data_dir.maybe_create_child_directory("RootBackup")?, install.install_dir());
+
+        Ok(())
+    }
+}
+
+/// A mods folder of files that are deployed to the games install directory rather than its data directory
+fn root_folder(mod_dir: &Path) -> Option<PathBuf> {
+    let path = mod_dir.join(find_case_insensitive_path(mod_dir, ROOT_FOLDER));
+    path.is_dir().then_some(path)
+}
+
+/// Copy a directory into a games directory, moving files that were there before deploying into `backup_dir`
+///
+/// `deployed` collects the paths created relative to `target`, files already in it came from an earlier mod
+/// and are overwritten.
+fn deploy_files(from: &Path, target: &Path, backup_dir: &ExistingDirectory, skip: Option<&Path>, method: DeployMethod, deployed: &mut Vec<PathBuf>) -> Result<(), ToryggError> {
+    let entries = WalkDir::new(from).min_depth(1).into_iter()
+        .filter_entry(|entry| Some(entry.path()) != skip);
+
+    for entry in entries {
+        let entry = entry.unwrap();
+        let path = entry.path();
+
+        let relative_path = path.strip_prefix(from).unwrap();
+        let to_relative_path = find_case_insensitive_path(target, relative_path);
+        let to_path = target.join(&to_relative_path);
+
+        if path.is_dir() {
+            if to_path.is_dir() {
+                continue;
+            }
+
+            fs::create_dir(&to_path)?;
+            deployed.push(to_relative_path);
+        } else {
+            info!("{} -> {}", relative_path.display(), to_path.display());
+
+            if deployed.contains(&to_relative_path) {
+                // Links cannot replace a file deployed by an earlier mod
+                fs::remove_file(&to_path)?;
+            } else if to_path.symlink_metadata().is_ok() {
+                let backup_path = backup_dir.as_ref().join(&to_relative_path);
+                for dir in to_relative_path.parent().unwrap() {
+                    let _ = backup_dir.maybe_create_child_directory(dir)?;
+                }
+                fs::rename(&to_path, &backup_path)?;
+            }
+
+            match method {
+                DeployMethod::Copy => fs::copy(path, &to_path).map(|_| ())?,
+                DeployMethod::Hardlink => fs::hard_link(path, &to_path)?,
+                DeployMethod::Symlink => std::os::unix::fs::symlink(path, &to_path)?,
+            }
+
+            if !deployed.contains(&to_relative_path) {
+                deployed.push(to_relative_path);
             }
         }
+    }
 
-        Ok(())
+    Ok(())
+}
+
+/// Remove deployed files, in reverse so directories are empty by the time they are removed
+fn remove_files(target: &Path, deployed: &[PathBuf]) -> Result<(), ToryggError> {
+    for relative_path in deployed.iter().rev() {
+        let path = target.join(relative_path);
+        if path.is_dir() {
+            fs::remove_dir(path)?;
+        } else if path.symlink_metadata().is_ok() {
+            // A deploy that failed part-way may have removed a file without replacing it
+            fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn restore_backups(backup_dir: &ExistingDirectory, target: &Path) {
+    for entry in WalkDir::new(backup_dir).min_depth(1).contents_first(true) {
+        let entry = entry.unwrap();
+        let path = entry.path();
+        let relative_path = path.strip_prefix(backup_dir).unwrap();
+        let to_path = target.join(relative_path);
+
+        if path.is_file() {
+            info!("{}", relative_path.display());
+            fs::rename(path, to_path).unwrap();
+        } else {
+            fs::remove_dir(path).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A state kept in `dir`, managing a game installed there
+    fn state(dir: &Path) -> ToryggState {
+        let context = Context::new(dir.join("config"), dir.join("data")).unwrap();
+        let install_dir = dir.join("game");
+        fs::create_dir_all(install_dir.join("Data")).unwrap();
+        fs::write(install_dir.join("Data/Skyrim.esm"), "game").unwrap();
+
+        let mut state = ToryggState::read_or_new(context).unwrap();
+        state.set_install_source(InstallSource::Manual { install_dir, prefix: None }).unwrap();
+        state
+    }
+
+    #[test]
+    fn contexts() {
+        let (a, b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let mut first = state(a.path());
+        let second = state(b.path());
+
+        let name = "Mod".to_owned();
+        first.create_mod(&name).unwrap();
+        fs::write(first.context().mods_dir(first.game()).unwrap().as_ref().join("Mod/Skyrim.esm"), "mod").unwrap();
+        first.activate_mod(&name).unwrap();
+        assert_eq!(first.mods().unwrap(), [name]);
+        assert!(second.mods().unwrap().is_empty());
+
+        first.deploy().unwrap();
+        assert_eq!(fs::read_to_string(a.path().join("game/Data/Skyrim.esm")).unwrap(), "mod");
+        assert_eq!(fs::read_to_string(b.path().join("game/Data/Skyrim.esm")).unwrap(), "game");
+        assert!(ToryggState::read_or_new(first.context().clone()).unwrap().deployed());
+        assert!(!ToryggState::read_or_new(second.context().clone()).unwrap().deployed());
+
+        first.undeploy().unwrap();
+        assert_eq!(fs::read_to_string(a.path().join("game/Data/Skyrim.esm")).unwrap(), "game");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn invalid_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = state(dir.path()).context().clone();
+        let path = ToryggState::path(&context);
+
+        fs::write(&path, "game = ").unwrap();
+        assert!(matches!(ToryggState::read_or_new(context.clone()), Err(ToryggError::InvalidConfig { .. })));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "game = ");
+
+        fs::write(&path, "game = \"unknown\"\nprofile = \"Default\"\n").unwrap();
+        assert!(matches!(ToryggState::read_or_new(context.clone()), Err(ToryggError::GameNotFound(_))));
+        assert!(fs::read_to_string(&path).unwrap().contains("unknown"));
+    }
+
+    #[test]
+    fn root_deploy() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let mut state = state(dir);
+        fs::write(dir.join("game/skse64_loader.exe"), "old loader").unwrap();
+
+        let name = "SKSE".to_owned();
+        state.create_mod(&name).unwrap();
+        let mod_dir = state.context().mods_dir(state.game()).unwrap().as_ref().join("SKSE");
+        for (path, text) in [("root/skse64_loader.exe", "loader"), ("root/skse64_1_6_1170.dll", "dll"), ("Scripts/skse.pex", "script")] {
+            fs::create_dir_all(mod_dir.join(path).parent().unwrap()).unwrap();
+            fs::write(mod_dir.join(path), text).unwrap();
+        }
+        state.activate_mod(&name).unwrap();
+
+        state.deploy().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("game/skse64_loader.exe")).unwrap(), "loader");
+        assert_eq!(fs::read_to_string(dir.join("game/skse64_1_6_1170.dll")).unwrap(), "dll");
+        assert_eq!(fs::read_to_string(dir.join("game/Data/Scripts/skse.pex")).unwrap(), "script");
+        assert!(!dir.join("game/Data/root").exists());
+        let backup = state.context().game_data_dir(state.game()).unwrap().as_ref().join("RootBackup/skse64_loader.exe");
+        assert_eq!(fs::read_to_string(backup).unwrap(), "old loader");
+
+        state.undeploy().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("game/skse64_loader.exe")).unwrap(), "old loader");
+        assert!(!dir.join("game/skse64_1_6_1170.dll").exists());
+        assert!(!dir.join("game/Data/Scripts").exists());
+    }
+
+    #[test]
+    fn failed_deploy() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let mut state = state(dir);
+        fs::write(dir.join("game/Data/Textures"), "not a directory").unwrap();
+        fs::write(dir.join("game/Data/Mod.esp"), "old plugin").unwrap();
+        fs::write(dir.join("game/skse64_loader.exe"), "old loader").unwrap();
+
+        let mods_dir = state.context().mods_dir(state.game()).unwrap().as_ref().to_path_buf();
+        for (path, text) in [("A/Mod.esp", "plugin"), ("A/Root/skse64_loader.exe", "loader"), ("B/Textures/a.dds", "texture")] {
+            fs::create_dir_all(mods_dir.join(path).parent().unwrap()).unwrap();
+            fs::write(mods_dir.join(path), text).unwrap();
+        }
+        for name in ["A", "B"] {
+            state.activate_mod(&name.to_owned()).unwrap();
+        }
+
+        // The second mod cannot create its Textures directory
+        assert!(state.deploy().is_err());
+        assert!(state.deployed());
+        assert!(ToryggState::read(state.context()).unwrap().deployed());
+        assert_eq!(fs::read_to_string(dir.join("game/Data/Mod.esp")).unwrap(), "plugin");
+
+        state.undeploy().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("game/Data/Mod.esp")).unwrap(), "old plugin");
+        assert_eq!(fs::read_to_string(dir.join("game/skse64_loader.exe")).unwrap(), "old loader");
+        assert_eq!(fs::read_to_string(dir.join("game/Data/Textures")).unwrap(), "not a directory");
+    }
+
+    /// Install a compatibility tool whose `proton` acts as a modding tool, writing a new plugin and
+    /// changing `Mod.esp` in the data directory, or failing for `fail.exe`
+    fn stub_tool(dir: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tool = dir.join("steam/compatibilitytools.d/stub");
+        fs::create_dir_all(&tool).unwrap();
+        fs::write(dir.join("steam/config/libraryfolders.vdf"), "libraryfolders { }").unwrap();
+        fs::write(tool.join("compatibilitytool.vdf"), "compatibilitytools { compat_tools { stub { install_path . } } }").unwrap();
+        fs::write(tool.join("proton"), "#!/bin/sh\ncase \"$2\" in *fail.exe) exit 1;; esac\nprintf generated > Data/Output.esp\nprintf changed > Data/Mod.esp\n").unwrap();
+        fs::set_permissions(tool.join("proton"), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn executables() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::create_dir_all(dir.join("steam/config")).unwrap();
+        fs::create_dir_all(dir.join("compatdata/pfx")).unwrap();
+        stub_tool(dir);
+
+        let mut state = state(dir);
+        *state.context_mut() = state.context().clone().with_steam_root(dir.join("steam"));
+        state.set_install_source(InstallSource::Manual { install_dir: dir.join("game"), prefix: Some(dir.join("compatdata/pfx")) }).unwrap();
+        for exe in ["tool.exe", "fail.exe"] {
+            fs::write(dir.join("game").join(exe), "").unwrap();
+        }
+
+        let tool = Executable::new("Tool", "tool.exe").with_args(vec!["-quick".to_owned()]).with_output_mod("Output");
+        state.add_executable(tool.clone()).unwrap();
+        state.add_executable(Executable::new("Fail", "fail.exe")).unwrap();
+        state.add_executable(Executable::new("Blocked", "tool.exe").with_output_mod("Blocked")).unwrap();
+        assert!(matches!(state.add_executable(tool.clone()), Err(ToryggError::ExecutableAlreadyExists(_))));
+        assert_eq!(executable::read(state.context(), state.game()).unwrap()[0], tool);
+
+        // Deployed by linking, which the tool must not change through
+        let mut settings = state.context().settings().clone();
+        settings.set("deploy_method", "hardlink").unwrap();
+        state.context_mut().set_settings(settings).unwrap();
+
+        let mods_dir = state.context().mods_dir(state.game()).unwrap().as_ref().to_path_buf();
+        let name = "Mod".to_owned();
+        state.create_mod(&name).unwrap();
+        fs::write(mods_dir.join("Mod/Mod.esp"), "mod").unwrap();
+        state.activate_mod(&name).unwrap();
+
+        state.run_executable("Tool", Some("stub")).unwrap();
+        assert_eq!(fs::read_to_string(mods_dir.join("Output/Output.esp")).unwrap(), "generated");
+        assert_eq!(fs::read_to_string(mods_dir.join("Output/Mod.esp")).unwrap(), "changed");
+        assert_eq!(fs::read_to_string(mods_dir.join("Mod/Mod.esp")).unwrap(), "mod");
+        assert!(state.profile().mod_enabled(&"Output".to_owned()));
+        assert!(!state.deployed());
+        assert!(!dir.join("game/Data/Output.esp").exists() && !dir.join("game/Data/Mod.esp").exists());
+
+        // Undeployed when the tool or collecting its output fails
+        assert!(matches!(state.run_executable("Fail", Some("stub")), Err(ToryggError::ChildFailed)));
+        assert!(!state.deployed() && !dir.join("game/Data/Mod.esp").exists());
+
+        fs::write(mods_dir.join("Blocked"), "").unwrap();
+        assert!(state.run_executable("Blocked", Some("stub")).is_err());
+        assert!(!state.deployed() && !dir.join("game/Data/Output.esp").exists());
+        assert_eq!(fs::read_dir(dir.join("game/Data")).unwrap().count(), 1);
+
+        state.remove_executable("Fail").unwrap();
+        assert!(matches!(state.remove_executable("Fail"), Err(ToryggError::UnknownExecutable(_))));
+        assert_eq!(state.executables().unwrap().len(), 2);
+    }
+}
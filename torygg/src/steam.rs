@@ -0,0 +1,373 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use log::warn;
+use serde::Deserialize;
+use torygg_vdf::{Options, Vdf};
+use crate::error::ToryggError;
+
+/// Where steam may be installed, in the order they are checked
+///
+/// Covers the `~/.steam` symlinks, a native install and the Flatpak and Snap packages.
+#[must_use]
+pub fn steam_root_candidates() -> Vec<PathBuf> {
+    let Some(home) = dirs::home_dir() else {
+        return Vec::new()
+    };
+
+    [
+        ".steam/root",
+        ".steam/steam",
+        ".local/share/Steam",
+        ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+        "snap/steam/common/.local/share/Steam",
+    ].iter().map(|path| home.join(path)).collect()
+}
+
+pub(crate) fn is_steam_root(path: &Path) -> bool {
+    path.join("config/libraryfolders.vdf").is_file()
+}
+
+/// The steam installation directory, `chosen` or else the first of the [`steam_root_candidates`] that is one
+///
+/// See [`crate::Context::steam_root`] for the directory chosen by the settings.
+///
+/// # Errors
+/// Errors with every location tried when none of them is a steam installation, or when there are none to
+/// try as the home directory is unknown
+pub fn steam_root(chosen: Option<PathBuf>) -> Result<PathBuf, ToryggError> {
+    let candidates = chosen.map_or_else(steam_root_candidates, |path| vec![path]);
+    if candidates.is_empty() {
+        return Err(ToryggError::HomeNotFound);
+    }
+
+    candidates.iter()
+        .find(|path| is_steam_root(path))
+        .cloned()
+        .ok_or(ToryggError::SteamNotFound(candidates))
+}
+
+pub(crate) fn libraryfolders_vdf(steam_root: &Path) -> PathBuf {
+    steam_root.join("config/libraryfolders.vdf")
+}
+
+pub(crate) fn appinfo_vdf(steam_root: &Path) -> PathBuf {
+    steam_root.join("appcache/appinfo.vdf")
+}
+
+/// A steam library folder from `libraryfolders.vdf`
+#[derive(Debug, Clone)]
+pub struct SteamLibrary {
+    path: PathBuf,
+    apps: Vec<usize>,
+}
+
+impl SteamLibrary {
+    /// Every library of a steam installation, in the order steam lists them
+    ///
+    /// Entries of `libraryfolders` other than library objects, such as `contentstatsid`, are skipped.
+    ///
+    /// # Errors
+    /// Errors when `libraryfolders.vdf` cannot be read
+    pub fn all(steam_root: &Path) -> Result<Vec<SteamLibrary>, ToryggError> {
+        let root = torygg_vdf::parse_file(libraryfolders_vdf(steam_root), &Options::default())?;
+
+        let mut libraries = Vec::new();
+        for (index, folder) in root.get("libraryfolders").and_then(Vdf::as_object).into_iter().flatten() {
+            let Some(path) = folder.get("path").and_then(Vdf::as_str) else {
+                continue;
+            };
+
+            let apps = folder.get("apps").and_then(Vdf::as_object).into_iter().flatten()
+                .filter_map(|(appid, _)| appid.parse().ok())
+                .collect();
+            libraries.push((index.parse().unwrap_or(usize::MAX), SteamLibrary { path: PathBuf::from(path), apps }));
+        }
+        libraries.sort_by_key(|(index, _)| *index);
+
+        Ok(libraries.into_iter().map(|(_, library)| library).collect())
+    }
+
+    /// Find the library an app is installed in and read its manifest
+    ///
+    /// # Errors
+    /// Errors when the libraries cannot be read or no library has a manifest for the app
+    pub fn find_app(steam_root: &Path, appid: usize) -> Result<(SteamLibrary, AppManifest), ToryggError> {
+        // Libraries that list the app are checked first, the list is only updated by steam now and then
+        let mut libraries = Self::all(steam_root)?;
+        libraries.sort_by_key(|library| !library.apps.contains(&appid));
+
+        for library in libraries {
+            if let Some(manifest) = library.manifest(appid)? {
+                return Ok((library, manifest));
+            }
+        }
+
+        Err(ToryggError::AppNotInstalled(appid))
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[must_use]
+    pub fn steamapps_dir(&self) -> PathBuf {
+        self.path.join("steamapps")
+    }
+
+    /// The apps `libraryfolders.vdf` lists for this library
+    #[must_use]
+    pub fn apps(&self) -> &[usize] {
+        &self.apps
+    }
+
+    /// Read the manifest of an app installed in this library
+    ///
+    /// # Errors
+    /// Errors when the manifest exists but cannot be read or parsed
+    pub fn manifest(&self, appid: usize) -> Result<Option<AppManifest>, ToryggError> {
+        let path = self.steamapps_dir().join(format!("appmanifest_{appid}.acf"));
+        if path.is_file() {
+            AppManifest::read(&path, &self.path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Read the manifest of every app installed in this library, skipping any that cannot be read
+    ///
+    /// # Errors
+    /// Errors when the steamapps directory cannot be read
+    pub fn manifests(&self) -> Result<Vec<AppManifest>, ToryggError> {
+        let mut manifests = Vec::new();
+        for entry in fs::read_dir(self.steamapps_dir())? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if name.starts_with("appmanifest_") && name.ends_with(".acf") {
+                match AppManifest::read(&path, &self.path) {
+                    Ok(manifest) => manifests.push(manifest),
+                    Err(e) => warn!("Skipping {}: {e}", path.display()),
+                }
+            }
+        }
+
+        manifests.sort_by_key(AppManifest::appid);
+        Ok(manifests)
+    }
+}
+
+/// The `StateFlags` of an app manifest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateFlags(u32);
+
+impl StateFlags {
+    pub const UPDATE_REQUIRED: u32 = 2;
+    pub const FULLY_INSTALLED: u32 = 4;
+    pub const FILES_MISSING: u32 = 32;
+    pub const APP_RUNNING: u32 = 64;
+    pub const FILES_CORRUPT: u32 = 128;
+    pub const UPDATE_RUNNING: u32 = 256;
+    pub const UPDATE_PAUSED: u32 = 512;
+    pub const UPDATE_STARTED: u32 = 1024;
+    pub const UNINSTALLING: u32 = 2048;
+    pub const VALIDATING: u32 = 131_072;
+    pub const ADDING_FILES: u32 = 262_144;
+    pub const PREALLOCATING: u32 = 524_288;
+    pub const DOWNLOADING: u32 = 1_048_576;
+    pub const STAGING: u32 = 2_097_152;
+    pub const COMMITTING: u32 = 4_194_304;
+
+    /// Flags set while steam is changing the games files
+    const CHANGING_FILES: u32 = Self::UPDATE_RUNNING | Self::UPDATE_STARTED | Self::UNINSTALLING | Self::VALIDATING
+        | Self::ADDING_FILES | Self::PREALLOCATING | Self::DOWNLOADING | Self::STAGING | Self::COMMITTING;
+
+    #[must_use]
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    #[must_use]
+    pub fn contains(self, flag: u32) -> bool {
+        self.0 & flag == flag
+    }
+
+    #[must_use]
+    pub fn is_installed(self) -> bool {
+        self.contains(Self::FULLY_INSTALLED)
+    }
+
+    #[must_use]
+    pub fn update_required(self) -> bool {
+        self.contains(Self::UPDATE_REQUIRED)
+    }
+
+    /// Whether steam is updating, validating or uninstalling the app
+    #[must_use]
+    pub fn is_updating(self) -> bool {
+        self.0 & Self::CHANGING_FILES != 0
+    }
+}
+
+#[derive(Deserialize)]
+struct Acf {
+    app_state: AppState,
+}
+
+#[derive(Deserialize)]
+struct AppState {
+    appid: usize,
+    #[serde(default)]
+    name: String,
+    installdir: String,
+    #[serde(default)]
+    buildid: u64,
+    #[serde(default)]
+    state_flags: u32,
+    #[serde(default)]
+    last_updated: u64,
+}
+
+/// An app's `appmanifest_<appid>.acf`, which steam keeps up to date as it installs and updates the app
+#[derive(Debug, Clone)]
+pub struct AppManifest {
+    appid: usize,
+    name: String,
+    install_dir: PathBuf,
+    buildid: u64,
+    state_flags: StateFlags,
+    last_updated: u64,
+}
+
+impl AppManifest {
+    /// Read a manifest from a library
+    ///
+    /// # Errors
+    /// Errors when the file cannot be read or parsed
+    pub fn read(path: &Path, library: &Path) -> Result<AppManifest, ToryggError> {
+        let mut file = File::open(path)?;
+        let acf = torygg_vdf::serde::from_reader::<_, Acf>(&mut file)
+            .map_err(|e| ToryggError::Other(format!("failed to parse {}: {e}", path.display())))?;
+
+        let state = acf.app_state;
+        Ok(AppManifest {
+            appid: state.appid,
+            name: state.name,
+            install_dir: library.join("steamapps/common").join(state.installdir),
+            buildid: state.buildid,
+            state_flags: StateFlags(state.state_flags),
+            last_updated: state.last_updated,
+        })
+    }
+
+    #[must_use]
+    pub fn appid(&self) -> usize {
+        self.appid
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The apps installation directory, from `installdir`
+    #[must_use]
+    pub fn install_dir(&self) -> &Path {
+        &self.install_dir
+    }
+
+    #[must_use]
+    pub fn buildid(&self) -> u64 {
+        self.buildid
+    }
+
+    #[must_use]
+    pub fn state_flags(&self) -> StateFlags {
+        self.state_flags
+    }
+
+    /// When the app was last updated
+    #[must_use]
+    pub fn last_updated(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.last_updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write a steam installation with one library in `library` holding the manifests
+    fn steam(root: &Path, library: &Path, manifests: &[(&str, &str)]) {
+        fs::create_dir_all(root.join("config")).unwrap();
+        fs::write(libraryfolders_vdf(root), format!(r#""libraryfolders"
+{{
+	"0"
+	{{
+		"path"		"{}"
+		"apps"
+		{{
+			"228980"		"1"
+		}}
+	}}
+	"1"
+	{{
+		"path"		"{}"
+		"apps"
+		{{
+			"489830"		"1"
+			"377160"		"1"
+		}}
+	}}
+	"contentstatsid"		"-4629118470347946127"
+}}
+"#, root.display(), library.display())).unwrap();
+
+        fs::create_dir_all(library.join("steamapps")).unwrap();
+        for (name, text) in manifests {
+            fs::write(library.join("steamapps").join(name), text).unwrap();
+        }
+    }
+
+    #[test]
+    fn libraries() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, library) = (dir.path().join("steam"), dir.path().join("games"));
+        steam(&root, &library, &[
+            ("appmanifest_489830.acf", r#""AppState"
+{
+	"appid"		"489830"
+	"name"		"The Elder Scrolls V: Skyrim Special Edition"
+	"StateFlags"		"1542"
+	"installdir"		"Skyrim Special Edition"
+	"LastUpdated"		"1700000000"
+	"buildid"		"13064958"
+}
+"#),
+            ("appmanifest_377160.acf", "\"AppState\" { \"name\" \"no appid or installdir\" }"),
+        ]);
+
+        let libraries = SteamLibrary::all(&root).unwrap();
+        assert_eq!(libraries.iter().map(SteamLibrary::path).collect::<Vec<_>>(), [root.as_path(), library.as_path()]);
+        assert_eq!(libraries[0].apps(), [228_980]);
+
+        let manifests = libraries[1].manifests().unwrap();
+        assert_eq!(manifests.iter().map(AppManifest::appid).collect::<Vec<_>>(), [489_830]);
+
+        let (found, manifest) = SteamLibrary::find_app(&root, 489_830).unwrap();
+        assert_eq!(found.path(), library);
+        assert_eq!(manifest.name(), "The Elder Scrolls V: Skyrim Special Edition");
+        assert_eq!(manifest.install_dir(), library.join("steamapps/common/Skyrim Special Edition"));
+        assert_eq!(manifest.buildid(), 13_064_958);
+        assert_eq!(manifest.last_updated(), SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+
+        // 1542 is installed, needing an update that was started and paused
+        let flags = manifest.state_flags();
+        assert!(flags.is_installed() && flags.update_required() && flags.is_updating());
+        assert!(!flags.contains(StateFlags::FILES_MISSING));
+        assert!(!StateFlags(StateFlags::FULLY_INSTALLED).is_updating());
+
+        assert!(matches!(SteamLibrary::find_app(&root, 377_160), Err(ToryggError::Other(_))));
+        assert!(matches!(SteamLibrary::find_app(&root, 228_980), Err(ToryggError::AppNotInstalled(228_980))));
+    }
+}